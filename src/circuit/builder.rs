@@ -0,0 +1,462 @@
+use std::iter::zip;
+
+use super::{
+    commitment::WireSource,
+    error::CircuitParseError,
+    gate::{GateKind, GateType},
+    indexed::{Circuit, IndexedGate, WireId},
+    BristolCircuit, BristolFormat,
+};
+
+/// Composes circuits in Rust instead of hand writing Bristol files. Wires handed out by the
+/// builder are only meaningful to it, `build` renumbers them so inputs come first and outputs
+/// last as Bristol expects. Multi-bit values are slices of wires, least significant bit first.
+#[derive(Default)]
+pub struct CircuitBuilder {
+    gates: Vec<IndexedGate>,
+    num_wires: usize,
+    inputs: Vec<Vec<WireId>>,
+    outputs: Vec<Vec<WireId>>,
+    constants: [Option<WireId>; 2],
+}
+
+impl CircuitBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn new_wire(&mut self) -> WireId {
+        self.num_wires += 1;
+        self.num_wires - 1
+    }
+
+    fn gate(&mut self, kind: GateKind, input_wires: Vec<WireId>) -> WireId {
+        let output = self.new_wire();
+        self.gates.push(IndexedGate {
+            kind,
+            input_wires,
+            output_wires: vec![output],
+        });
+        output
+    }
+
+    /// Declares the next circuit input, returning its wires
+    pub fn input(&mut self, size: usize) -> Vec<WireId> {
+        let wires = (0..size).map(|_| self.new_wire()).collect::<Vec<WireId>>();
+        self.inputs.push(wires.clone());
+        wires
+    }
+
+    /// Declares the next circuit output
+    pub fn output(&mut self, wires: &[WireId]) {
+        self.outputs.push(wires.to_vec());
+    }
+
+    pub fn constant(&mut self, value: bool) -> WireId {
+        match self.constants[value as usize] {
+            Some(wire) => wire,
+            None => {
+                let wire = self.gate(GateKind::Constant(value), vec![]);
+                self.constants[value as usize] = Some(wire);
+                wire
+            }
+        }
+    }
+
+    pub fn constant_word(&mut self, value: u128, size: usize) -> Vec<WireId> {
+        (0..size)
+            .map(|i| self.constant(i < 128 && (value >> i) & 1 == 1))
+            .collect()
+    }
+
+    pub fn and(&mut self, a: WireId, b: WireId) -> WireId {
+        self.gate(GateKind::Op(GateType::AND), vec![a, b])
+    }
+
+    pub fn xor(&mut self, a: WireId, b: WireId) -> WireId {
+        self.gate(GateKind::Op(GateType::XOR), vec![a, b])
+    }
+
+    pub fn not(&mut self, a: WireId) -> WireId {
+        self.gate(GateKind::Op(GateType::NOT), vec![a])
+    }
+
+    pub fn or(&mut self, a: WireId, b: WireId) -> WireId {
+        self.gate(GateKind::Op(GateType::OR), vec![a, b])
+    }
+
+    pub fn nand(&mut self, a: WireId, b: WireId) -> WireId {
+        self.gate(GateKind::Op(GateType::NAND), vec![a, b])
+    }
+
+    pub fn nor(&mut self, a: WireId, b: WireId) -> WireId {
+        self.gate(GateKind::Op(GateType::NOR), vec![a, b])
+    }
+
+    pub fn xnor(&mut self, a: WireId, b: WireId) -> WireId {
+        self.gate(GateKind::Op(GateType::XNOR), vec![a, b])
+    }
+
+    /// Same as `mux` but as a single MUX gate, for circuits that don't need to stay within the
+    /// basic gate set
+    pub fn mux_gate(&mut self, select: WireId, a: WireId, b: WireId) -> WireId {
+        self.gate(GateKind::Op(GateType::MUX), vec![select, a, b])
+    }
+
+    /// `b` if `select` is set, otherwise `a`
+    pub fn mux(&mut self, select: WireId, a: WireId, b: WireId) -> WireId {
+        let diff = self.xor(a, b);
+        let masked = self.and(select, diff);
+        self.xor(a, masked)
+    }
+
+    pub fn and_word(&mut self, a: &[WireId], b: &[WireId]) -> Vec<WireId> {
+        assert_eq!(a.len(), b.len(), "words must have the same width");
+        zip(a, b).map(|(a, b)| self.and(*a, *b)).collect()
+    }
+
+    pub fn xor_word(&mut self, a: &[WireId], b: &[WireId]) -> Vec<WireId> {
+        assert_eq!(a.len(), b.len(), "words must have the same width");
+        zip(a, b).map(|(a, b)| self.xor(*a, *b)).collect()
+    }
+
+    pub fn not_word(&mut self, a: &[WireId]) -> Vec<WireId> {
+        a.iter().map(|a| self.not(*a)).collect()
+    }
+
+    pub fn mux_word(&mut self, select: WireId, a: &[WireId], b: &[WireId]) -> Vec<WireId> {
+        assert_eq!(a.len(), b.len(), "words must have the same width");
+        zip(a, b).map(|(a, b)| self.mux(select, *a, *b)).collect()
+    }
+
+    /// Ripple carry addition, returning the sum and the carry out
+    pub fn add_with_carry(
+        &mut self,
+        a: &[WireId],
+        b: &[WireId],
+        carry: WireId,
+    ) -> (Vec<WireId>, WireId) {
+        assert_eq!(a.len(), b.len(), "words must have the same width");
+        let mut carry = carry;
+        let mut sum = Vec::with_capacity(a.len());
+        for (a, b) in zip(a, b) {
+            let a_carry = self.xor(*a, carry);
+            let b_carry = self.xor(*b, carry);
+            sum.push(self.xor(a_carry, *b));
+            // The carry is the majority of a, b and the incoming carry, using a single AND
+            let both = self.and(a_carry, b_carry);
+            carry = self.xor(both, carry);
+        }
+        (sum, carry)
+    }
+
+    /// Addition modulo 2^n
+    pub fn add(&mut self, a: &[WireId], b: &[WireId]) -> Vec<WireId> {
+        let zero = self.constant(false);
+        self.add_with_carry(a, b, zero).0
+    }
+
+    /// Subtraction modulo 2^n
+    pub fn sub(&mut self, a: &[WireId], b: &[WireId]) -> Vec<WireId> {
+        self.sub_with_borrow(a, b).0
+    }
+
+    /// Computes `a + !b + 1`, the carry out is clear exactly when `a < b`
+    fn sub_with_borrow(&mut self, a: &[WireId], b: &[WireId]) -> (Vec<WireId>, WireId) {
+        let not_b = self.not_word(b);
+        let one = self.constant(true);
+        let (difference, carry) = self.add_with_carry(a, &not_b, one);
+        (difference, self.not(carry))
+    }
+
+    /// Multiplication modulo 2^n, summing the shifted partial products
+    pub fn mul(&mut self, a: &[WireId], b: &[WireId]) -> Vec<WireId> {
+        assert_eq!(a.len(), b.len(), "words must have the same width");
+        let n = a.len();
+        let mut product = a
+            .iter()
+            .map(|a| self.and(*a, b[0]))
+            .collect::<Vec<WireId>>();
+        for i in 1..n {
+            // Bits of the partial product past the width of the result are dropped
+            let partial = a[..n - i]
+                .iter()
+                .map(|a| self.and(*a, b[i]))
+                .collect::<Vec<WireId>>();
+            let sum = self.add(&product[i..], &partial);
+            product.splice(i.., sum);
+        }
+        product
+    }
+
+    /// Unsigned `a < b`
+    pub fn less_than(&mut self, a: &[WireId], b: &[WireId]) -> WireId {
+        self.sub_with_borrow(a, b).1
+    }
+
+    /// Unsigned `a > b`
+    pub fn greater_than(&mut self, a: &[WireId], b: &[WireId]) -> WireId {
+        self.less_than(b, a)
+    }
+
+    pub fn equal(&mut self, a: &[WireId], b: &[WireId]) -> WireId {
+        assert_eq!(a.len(), b.len(), "words must have the same width");
+        let mut equal = self.constant(true);
+        for (a, b) in zip(a, b) {
+            let diff = self.xor(*a, *b);
+            let same = self.not(diff);
+            equal = self.and(equal, same);
+        }
+        equal
+    }
+
+    /// Shifts towards the most significant bit, filling with zeros. Shifts and rotations only
+    /// rewire, they don't add gates beyond the zero constant.
+    pub fn shift_left(&mut self, a: &[WireId], n: usize) -> Vec<WireId> {
+        let n = n.min(a.len());
+        let zero = self.constant(false);
+        let mut shifted = vec![zero; n];
+        shifted.extend_from_slice(&a[..a.len() - n]);
+        shifted
+    }
+
+    /// Logical shift towards the least significant bit, filling with zeros
+    pub fn shift_right(&mut self, a: &[WireId], n: usize) -> Vec<WireId> {
+        let n = n.min(a.len());
+        let zero = self.constant(false);
+        let mut shifted = a[n..].to_vec();
+        shifted.extend(vec![zero; n]);
+        shifted
+    }
+
+    pub fn rotate_left(&self, a: &[WireId], n: usize) -> Vec<WireId> {
+        let mut rotated = a.to_vec();
+        if !a.is_empty() {
+            rotated.rotate_right(n % a.len());
+        }
+        rotated
+    }
+
+    pub fn rotate_right(&self, a: &[WireId], n: usize) -> Vec<WireId> {
+        let mut rotated = a.to_vec();
+        if !a.is_empty() {
+            rotated.rotate_left(n % a.len());
+        }
+        rotated
+    }
+
+    /// Lays the circuit out in Bristol order. Gates that don't contribute to an output are
+    /// dropped, and an output that isn't a fresh gate output (an input, a constant or a wire
+    /// used twice) is copied through an EQW gate so every output wire is distinct.
+    pub fn build(mut self) -> Circuit {
+        let is_input = {
+            let mut is_input = vec![false; self.num_wires];
+            self.inputs
+                .iter()
+                .flatten()
+                .for_each(|w| is_input[*w] = true);
+            is_input
+        };
+        let mut is_output = vec![false; self.num_wires];
+        let outputs = std::mem::take(&mut self.outputs)
+            .into_iter()
+            .map(|output| {
+                output
+                    .into_iter()
+                    .map(|wire| {
+                        if is_input[wire] || is_output[wire] {
+                            let copy = self.gate(GateKind::Op(GateType::EQW), vec![wire]);
+                            is_output.push(true);
+                            copy
+                        } else {
+                            is_output[wire] = true;
+                            wire
+                        }
+                    })
+                    .collect::<Vec<WireId>>()
+            })
+            .collect::<Vec<Vec<WireId>>>();
+
+        // Keep only the gates an output depends on
+        let mut live = is_output.clone();
+        let mut gates = Vec::new();
+        for gate in self.gates.into_iter().rev() {
+            if gate.output_wires.iter().any(|w| live[*w]) {
+                gate.input_wires.iter().for_each(|w| live[*w] = true);
+                gates.push(gate);
+            }
+        }
+        gates.reverse();
+
+        // Inputs first, then intermediate wires in the order they were created, outputs last
+        let mut ids = vec![None; self.num_wires];
+        let mut next_id = 0;
+        let mut assign = |wire: WireId, ids: &mut Vec<Option<WireId>>| {
+            ids[wire] = Some(next_id);
+            next_id += 1;
+        };
+        self.inputs
+            .iter()
+            .flatten()
+            .for_each(|w| assign(*w, &mut ids));
+        for gate in gates.iter() {
+            for wire in gate.output_wires.iter() {
+                if !is_output[*wire] {
+                    assign(*wire, &mut ids);
+                }
+            }
+        }
+        outputs.iter().flatten().for_each(|w| assign(*w, &mut ids));
+        let num_wires = next_id;
+
+        let renumber = |wires: &Vec<WireId>| {
+            wires
+                .iter()
+                .map(|w| ids[*w].expect("wire is not driven by any gate"))
+                .collect()
+        };
+        Circuit {
+            gates: gates
+                .iter()
+                .map(|gate| IndexedGate {
+                    kind: gate.kind,
+                    input_wires: renumber(&gate.input_wires),
+                    output_wires: renumber(&gate.output_wires),
+                })
+                .collect(),
+            num_wires,
+            input_wire_sizes: self.inputs.iter().map(|input| input.len()).collect(),
+            output_wire_sizes: outputs.iter().map(|output| output.len()).collect(),
+            format: BristolFormat::Fashion,
+        }
+    }
+
+    pub fn build_bristol<'a>(
+        self,
+        wire_source: impl Into<WireSource<'a>>,
+    ) -> Result<BristolCircuit, CircuitParseError> {
+        BristolCircuit::from_circuit(&self.build(), wire_source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        circuit::{indexed::Circuit, tests::TEST_SECRET, BristolCircuit},
+        utils::conversions::Endianness,
+    };
+
+    use super::CircuitBuilder;
+
+    /// Builds a circuit taking two bytes and returning a single output
+    fn binary_op(
+        op: impl Fn(&mut CircuitBuilder, &[usize], &[usize]) -> Vec<usize>,
+    ) -> BristolCircuit {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.input(8);
+        let b = builder.input(8);
+        let output = op(&mut builder, &a, &b);
+        builder.output(&output);
+
+        builder.build_bristol(&TEST_SECRET).unwrap()
+    }
+
+    fn check(circuit: &mut BristolCircuit, expected: impl Fn(u8, u8) -> u8) {
+        for a in [0u8, 1, 7, 100, 128, 200, 255] {
+            for b in [0u8, 1, 7, 99, 100, 128, 255] {
+                let output = circuit
+                    .evaluate_values::<u8>(&[&a, &b], Endianness::Little)
+                    .unwrap();
+                assert_eq!(output, vec![expected(a, b)], "a = {}, b = {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut add = binary_op(|c, a, b| c.add(a, b));
+        add.validate().unwrap();
+        check(&mut add, u8::wrapping_add);
+        check(&mut binary_op(|c, a, b| c.sub(a, b)), u8::wrapping_sub);
+        check(&mut binary_op(|c, a, b| c.mul(a, b)), u8::wrapping_mul);
+    }
+
+    #[test]
+    fn test_comparisons() {
+        check(&mut binary_op(|c, a, b| vec![c.less_than(a, b)]), |a, b| {
+            (a < b) as u8
+        });
+        check(
+            &mut binary_op(|c, a, b| vec![c.greater_than(a, b)]),
+            |a, b| (a > b) as u8,
+        );
+        check(&mut binary_op(|c, a, b| vec![c.equal(a, b)]), |a, b| {
+            (a == b) as u8
+        });
+        check(&mut binary_op(|c, a, _| vec![c.equal(a, a)]), |_, _| 1);
+    }
+
+    #[test]
+    fn test_mux() {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.input(8);
+        let b = builder.input(8);
+        let select = builder.less_than(&a, &b);
+        let max = builder.mux_word(select, &a, &b);
+        builder.output(&max);
+        check(&mut builder.build_bristol(&TEST_SECRET).unwrap(), u8::max);
+    }
+
+    #[test]
+    fn test_shifts_and_rotations() {
+        check(&mut binary_op(|c, a, _| c.shift_left(a, 3)), |a, _| a << 3);
+        check(&mut binary_op(|c, a, _| c.shift_right(a, 3)), |a, _| a >> 3);
+        check(&mut binary_op(|c, a, _| c.rotate_left(a, 3)), |a, _| {
+            a.rotate_left(3)
+        });
+        check(&mut binary_op(|c, a, _| c.rotate_right(a, 11)), |a, _| {
+            a.rotate_right(11)
+        });
+        check(&mut binary_op(|c, a, _| c.shift_left(a, 9)), |_, _| 0);
+    }
+
+    #[test]
+    fn test_outputs_are_distinct_wires() {
+        // Outputs that are inputs, constants or repeated still get their own wires
+        let mut builder = CircuitBuilder::new();
+        let a = builder.input(2);
+        let one = builder.constant(true);
+        let both = builder.and(a[0], a[1]);
+        builder.output(&[a[0], one, both, both]);
+
+        let mut circuit = builder.build_bristol(&TEST_SECRET).unwrap();
+        circuit.validate().unwrap();
+        let output = circuit
+            .evaluate_values::<u8>(&[&3u8], Endianness::Little)
+            .unwrap();
+        assert_eq!(output, vec![0b1111]);
+    }
+
+    #[test]
+    fn test_round_trip_through_bristol() {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.input(32);
+        let b = builder.input(32);
+        let sum = builder.add(&a, &b);
+        let rotated = builder.rotate_right(&sum, 7);
+        let x = builder.xor_word(&rotated, &a);
+        builder.output(&x);
+        let circuit = builder.build();
+
+        let text = BristolCircuit::from_circuit(&circuit, &TEST_SECRET)
+            .unwrap()
+            .to_bristol();
+        assert_eq!(text.parse::<Circuit>().unwrap(), circuit);
+
+        let (a, b) = (0xdead_beefu32, 0x1234_5678u32);
+        let output = circuit
+            .evaluate_values::<u32>(&[&a, &b], Endianness::Little)
+            .unwrap();
+        assert_eq!(output, vec![a.wrapping_add(b).rotate_right(7) ^ a]);
+    }
+}
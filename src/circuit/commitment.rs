@@ -0,0 +1,161 @@
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    error::CircuitParseError,
+    gate::SafeWire,
+    wire::{HashTuple, Wire, WireSecret},
+    BristolCircuit,
+};
+
+/// The public half of the prover's wire commitments, holding only the hash pair of every wire
+/// in index order. This is what the prover sends to the verifier, who can rebuild the circuit
+/// from it and generate the equivocation and response scripts without ever seeing a preimage.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CircuitCommitment {
+    pub wire_hashes: Vec<HashTuple>,
+}
+
+impl BristolCircuit {
+    pub fn commitment(&self) -> CircuitCommitment {
+        CircuitCommitment {
+            wire_hashes: self
+                .wires
+                .iter()
+                .map(|wire_arcm| wire_arcm.lock().unwrap().get_hash_pair())
+                .collect(),
+        }
+    }
+}
+
+/// Where the wires of a loaded circuit get their commitments from. The prover derives them
+/// from its secret, while the verifier only has the public hashes.
+pub enum WireSource<'a> {
+    Secret(&'a WireSecret),
+    Commitment(&'a CircuitCommitment),
+}
+
+impl<'a> From<&'a WireSecret> for WireSource<'a> {
+    fn from(secret: &'a WireSecret) -> Self {
+        WireSource::Secret(secret)
+    }
+}
+
+impl<'a> From<&'a CircuitCommitment> for WireSource<'a> {
+    fn from(commitment: &'a CircuitCommitment) -> Self {
+        WireSource::Commitment(commitment)
+    }
+}
+
+impl WireSource<'_> {
+    pub fn create_wires(&self, num_wires: usize) -> Result<Vec<SafeWire>, CircuitParseError> {
+        let wires = match self {
+            WireSource::Secret(secret) => (0..num_wires).map(|i| Wire::new(i, secret)).collect(),
+            WireSource::Commitment(commitment) => {
+                if commitment.wire_hashes.len() != num_wires {
+                    return Err(CircuitParseError::CommitmentMismatch {
+                        expected: num_wires,
+                        found: commitment.wire_hashes.len(),
+                    });
+                }
+                commitment
+                    .wire_hashes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, hashes)| Wire::from_hashes(i, *hashes))
+                    .collect::<Vec<Wire>>()
+            }
+        };
+        Ok(wires
+            .into_iter()
+            .map(|wire| Arc::new(Mutex::new(wire)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::key::{Keypair, Secp256k1};
+
+    use crate::{
+        circuit::{error::CircuitParseError, tests::TEST_SECRET, BristolCircuit},
+        transactions::{
+            generate_equivocation_address_and_info, generate_response_address_and_info,
+        },
+        utils::challenge_hashes::ChallengeHashesManager,
+    };
+
+    use super::CircuitCommitment;
+
+    #[test]
+    fn test_verifier_circuit_from_commitment() {
+        let prover_circuit =
+            BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
+
+        // The commitment is sent to the verifier as json
+        let json = serde_json::to_string(&prover_circuit.commitment()).unwrap();
+        let commitment: CircuitCommitment = serde_json::from_str(&json).unwrap();
+        assert_eq!(commitment, prover_circuit.commitment());
+
+        let verifier_circuit =
+            BristolCircuit::from_bristol("circuits/add.txt", &commitment).unwrap();
+        assert!(verifier_circuit
+            .wires
+            .iter()
+            .all(|wire| wire.lock().unwrap().preimages.is_none()));
+
+        let secp = Secp256k1::new();
+        let prover_pk = Keypair::from_seckey_slice(&secp, &[1; 32])
+            .unwrap()
+            .x_only_public_key()
+            .0;
+        let verifier_pk = Keypair::from_seckey_slice(&secp, &[2; 32])
+            .unwrap()
+            .x_only_public_key()
+            .0;
+
+        let (prover_address, _) =
+            generate_equivocation_address_and_info(&secp, &prover_circuit, prover_pk, verifier_pk);
+        let (verifier_address, _) = generate_equivocation_address_and_info(
+            &secp,
+            &verifier_circuit,
+            prover_pk,
+            verifier_pk,
+        );
+        assert_eq!(prover_address, verifier_address);
+
+        let (challenge_hashes, _) = ChallengeHashesManager::new()
+            .generate_challenge_hashes(prover_circuit.gates.len(), Some(0));
+        let (prover_address, _) = generate_response_address_and_info(
+            &secp,
+            &prover_circuit,
+            prover_pk,
+            &challenge_hashes,
+        );
+        let (verifier_address, _) = generate_response_address_and_info(
+            &secp,
+            &verifier_circuit,
+            prover_pk,
+            &challenge_hashes,
+        );
+        assert_eq!(prover_address, verifier_address);
+    }
+
+    #[test]
+    fn test_commitment_must_match_circuit() {
+        let commitment =
+            BristolCircuit::from_str("1 3\n2 1 1\n1 1\n\n2 1 0 1 2 AND\n", &TEST_SECRET)
+                .unwrap()
+                .commitment();
+
+        let result = BristolCircuit::from_bristol("circuits/add.txt", &commitment);
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::CommitmentMismatch {
+                expected: 504,
+                found: 3
+            })
+        ));
+    }
+}
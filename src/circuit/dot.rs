@@ -0,0 +1,153 @@
+use std::io::{self, Write};
+
+use super::{gate::SafeWire, BristolCircuit};
+
+fn wire_index(wire: &SafeWire) -> usize {
+    wire.lock().unwrap().index.expect("wire index is not set")
+}
+
+/// What to include when exporting a circuit to Graphviz
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DotOptions {
+    /// Label wires with the bit they got in the last `evaluate` call
+    pub show_values: bool,
+    /// Gate to highlight, e.g. the one the verifier challenged
+    pub challenged_gate: Option<usize>,
+    /// Wire to highlight, e.g. one the prover revealed both preimages of
+    pub contradicting_wire: Option<usize>,
+}
+
+impl BristolCircuit {
+    /// Writes the circuit as a Graphviz digraph. Gates are nodes labelled with their position
+    /// and type, wires are edges from the gate or input that writes them to every gate that
+    /// reads them, labelled with the wire index.
+    pub fn write_dot(&self, mut writer: impl Write, options: &DotOptions) -> io::Result<()> {
+        let num_wires = self.wires.len();
+        let num_inputs = self.input_wire_sizes.iter().sum::<usize>();
+        let output_start = num_wires - self.output_wire_sizes.iter().sum::<usize>();
+
+        // Node that writes each wire, inputs are their own source nodes
+        let mut sources = (0..num_wires)
+            .map(|wire| (wire < num_inputs).then(|| format!("in{}", wire)))
+            .collect::<Vec<Option<String>>>();
+        for (i, gate) in self.gates.iter().enumerate() {
+            for wire in gate.get_output_wires() {
+                sources[wire_index(wire)] = Some(format!("g{}", i));
+            }
+        }
+
+        let edge = |writer: &mut dyn Write, wire: usize, target: &str| -> io::Result<()> {
+            let Some(source) = &sources[wire] else {
+                return Ok(());
+            };
+            let mut label = format!("w{}", wire);
+            if options.show_values {
+                if let Some(bit) = self.wires[wire].lock().unwrap().selector {
+                    label += &format!(" = {}", bit as u8);
+                }
+            }
+            let highlight = if options.contradicting_wire == Some(wire) {
+                ", color=red, fontcolor=red, penwidth=2"
+            } else {
+                ""
+            };
+            writeln!(
+                writer,
+                "  {} -> {} [label=\"{}\"{}];",
+                source, target, label, highlight
+            )
+        };
+
+        writeln!(writer, "digraph circuit {{")?;
+        writeln!(writer, "  rankdir=LR;")?;
+        writeln!(writer, "  node [fontname=\"monospace\"];")?;
+        for wire in 0..num_inputs {
+            writeln!(
+                writer,
+                "  in{} [label=\"in {}\", shape=circle];",
+                wire, wire
+            )?;
+        }
+        for wire in output_start..num_wires {
+            writeln!(
+                writer,
+                "  out{} [label=\"out {}\", shape=doublecircle];",
+                wire, wire
+            )?;
+        }
+        for (i, gate) in self.gates.iter().enumerate() {
+            let highlight = if options.challenged_gate == Some(i) {
+                ", style=filled, fillcolor=orange, penwidth=2"
+            } else {
+                ""
+            };
+            writeln!(
+                writer,
+                "  g{} [label=\"{}: {}\", shape=box{}];",
+                i,
+                i,
+                gate.get_name(),
+                highlight
+            )?;
+        }
+
+        for (i, gate) in self.gates.iter().enumerate() {
+            for wire in gate.get_input_wires() {
+                edge(&mut writer, wire_index(wire), &format!("g{}", i))?;
+            }
+        }
+        for wire in output_start..num_wires {
+            edge(&mut writer, wire, &format!("out{}", wire))?;
+        }
+        writeln!(writer, "}}")
+    }
+
+    pub fn to_dot(&self, options: &DotOptions) -> String {
+        let mut bytes = Vec::new();
+        self.write_dot(&mut bytes, options)
+            .expect("writing to a vec can't fail");
+        String::from_utf8(bytes).expect("dot output should be valid utf8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::{tests::TEST_SECRET, BristolCircuit};
+
+    use super::DotOptions;
+
+    fn circuit() -> BristolCircuit {
+        let text = "2 5\n2 1 1\n1 1\n\n2 1 0 1 2 XOR\n1 1 2 4 INV\n";
+        let mut circuit = BristolCircuit::from_str(text, &TEST_SECRET).unwrap();
+        // Wire 3 is unused, it has no source and no edges
+        circuit.evaluate(vec![vec![true], vec![false]]);
+        circuit
+    }
+
+    #[test]
+    fn test_dot_structure() {
+        let dot = circuit().to_dot(&DotOptions::default());
+        assert!(dot.starts_with("digraph circuit {"));
+        assert!(dot.trim_end().ends_with('}'));
+        assert!(dot.contains("g0 [label=\"0: XOR\", shape=box];"));
+        assert!(dot.contains("g1 [label=\"1: INV\", shape=box];"));
+        assert!(dot.contains("in0 -> g0 [label=\"w0\"];"));
+        assert!(dot.contains("in1 -> g0 [label=\"w1\"];"));
+        assert!(dot.contains("g0 -> g1 [label=\"w2\"];"));
+        assert!(dot.contains("g1 -> out4 [label=\"w4\"];"));
+        assert!(!dot.contains("w3"));
+    }
+
+    #[test]
+    fn test_dot_values_and_highlights() {
+        let dot = circuit().to_dot(&DotOptions {
+            show_values: true,
+            challenged_gate: Some(1),
+            contradicting_wire: Some(2),
+        });
+        assert!(dot.contains("in0 -> g0 [label=\"w0 = 1\"];"));
+        assert!(dot.contains("g1 -> out4 [label=\"w4 = 0\"];"));
+        assert!(dot.contains("g1 [label=\"1: INV\", shape=box, style=filled"));
+        assert!(dot.contains("g0 -> g1 [label=\"w2 = 1\", color=red"));
+    }
+}
@@ -0,0 +1,208 @@
+use std::{fmt, io};
+
+/// Errors produced while loading a Bristol circuit. Line and column numbers are 1-based and
+/// point at the offending token in the source text.
+#[derive(Debug)]
+pub enum CircuitParseError {
+    Io(io::Error),
+    InvalidHeader {
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+    InvalidNumber {
+        line: usize,
+        column: usize,
+        token: String,
+    },
+    UnexpectedEndOfLine {
+        line: usize,
+        column: usize,
+        expected: &'static str,
+    },
+    UnexpectedToken {
+        line: usize,
+        column: usize,
+        token: String,
+    },
+    WireIndexOutOfRange {
+        line: usize,
+        column: usize,
+        index: usize,
+        num_wires: usize,
+    },
+    UnknownGate {
+        line: usize,
+        column: usize,
+        name: String,
+    },
+    GateArityMismatch {
+        line: usize,
+        column: usize,
+        gate: String,
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+    GateCountMismatch {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    WireCountMismatch {
+        line: usize,
+        column: usize,
+        expected: usize,
+        found: usize,
+    },
+    CommitmentMismatch {
+        expected: usize,
+        found: usize,
+    },
+    UnknownCircuit {
+        name: String,
+    },
+}
+
+impl fmt::Display for CircuitParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitParseError::Io(e) => write!(f, "failed to read circuit: {}", e),
+            CircuitParseError::InvalidHeader {
+                line,
+                column,
+                reason,
+            } => write!(f, "{}:{}: invalid header: {}", line, column, reason),
+            CircuitParseError::InvalidNumber {
+                line,
+                column,
+                token,
+            } => write!(
+                f,
+                "{}:{}: expected a number, found '{}'",
+                line, column, token
+            ),
+            CircuitParseError::UnexpectedEndOfLine {
+                line,
+                column,
+                expected,
+            } => write!(
+                f,
+                "{}:{}: unexpected end of line, expected {}",
+                line, column, expected
+            ),
+            CircuitParseError::UnexpectedToken {
+                line,
+                column,
+                token,
+            } => write!(f, "{}:{}: unexpected token '{}'", line, column, token),
+            CircuitParseError::WireIndexOutOfRange {
+                line,
+                column,
+                index,
+                num_wires,
+            } => write!(
+                f,
+                "{}:{}: wire index {} is out of range for a circuit with {} wires",
+                line, column, index, num_wires
+            ),
+            CircuitParseError::UnknownGate { line, column, name } => {
+                write!(f, "{}:{}: unknown gate type '{}'", line, column, name)
+            }
+            CircuitParseError::GateArityMismatch {
+                line,
+                column,
+                gate,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}:{}: {} gate takes {} inputs and {} outputs, found {} and {}",
+                line, column, gate, expected.0, expected.1, found.0, found.1
+            ),
+            CircuitParseError::GateCountMismatch {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: header declares {} gates but {} were found",
+                line, expected, found
+            ),
+            CircuitParseError::WireCountMismatch {
+                line,
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}:{}: header declares {} wires but the circuit accounts for {}",
+                line, column, expected, found
+            ),
+            CircuitParseError::CommitmentMismatch { expected, found } => write!(
+                f,
+                "circuit has {} wires but the commitment has {} hash pairs",
+                expected, found
+            ),
+            CircuitParseError::UnknownCircuit { name } => {
+                write!(f, "no standard circuit named '{}'", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CircuitParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CircuitParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CircuitParseError {
+    fn from(e: io::Error) -> Self {
+        CircuitParseError::Io(e)
+    }
+}
+
+/// Structural problems found by `BristolCircuit::validate`. Gates are referred to by their
+/// position in the circuit and wires by their index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitValidationError {
+    /// The inputs and outputs declared in the header don't fit in the circuit's wires
+    HeaderMismatch { reason: String },
+    /// A gate reads a wire that isn't an input and hasn't been written by an earlier gate
+    WireReadBeforeWritten { gate: usize, wire: usize },
+    /// A wire is written by more than one gate, or is an input that a gate writes to
+    MultiplyDrivenWire { wire: usize, gate: usize },
+    /// An output wire that no gate writes to
+    UndrivenOutput { wire: usize },
+    /// A wire that is neither read by any gate nor an output of the circuit
+    UnusedWire { wire: usize },
+}
+
+impl fmt::Display for CircuitValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitValidationError::HeaderMismatch { reason } => {
+                write!(f, "header mismatch: {}", reason)
+            }
+            CircuitValidationError::WireReadBeforeWritten { gate, wire } => write!(
+                f,
+                "gate {} reads wire {} before any gate writes it",
+                gate, wire
+            ),
+            CircuitValidationError::MultiplyDrivenWire { wire, gate } => {
+                write!(f, "wire {} is driven again by gate {}", wire, gate)
+            }
+            CircuitValidationError::UndrivenOutput { wire } => {
+                write!(f, "output wire {} is never written", wire)
+            }
+            CircuitValidationError::UnusedWire { wire } => {
+                write!(f, "wire {} is never read and is not an output", wire)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CircuitValidationError {}
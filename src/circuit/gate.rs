@@ -1,8 +1,12 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
 
 use bitcoin::{
     opcodes::all::{
-        OP_BOOLAND, OP_EQUALVERIFY, OP_FROMALTSTACK, OP_NOT, OP_NUMEQUAL, OP_SHA256, OP_TOALTSTACK,
+        OP_BOOLAND, OP_BOOLOR, OP_DROP, OP_ELSE, OP_ENDIF, OP_EQUALVERIFY, OP_FROMALTSTACK, OP_IF,
+        OP_NIP, OP_NOT, OP_NUMEQUAL, OP_ROT, OP_SHA256, OP_TOALTSTACK,
     },
     script::Builder,
     ScriptBuf,
@@ -17,11 +21,106 @@ use super::wire::{HashValue, Wire};
 
 pub type SafeWire = Arc<Mutex<Wire>>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum GateType {
     AND,
     OR,
     XOR,
     NOT,
+    EQW,
+    NAND,
+    NOR,
+    XNOR,
+    /// Selects between its second and third input, `MUX(s, a, b)` is `b` when `s` is set
+    MUX,
+}
+
+impl GateType {
+    /// Number of input and output wires of the gate
+    pub fn arity(&self) -> (usize, usize) {
+        match self {
+            GateType::AND
+            | GateType::OR
+            | GateType::XOR
+            | GateType::NAND
+            | GateType::NOR
+            | GateType::XNOR => (2, 1),
+            GateType::NOT | GateType::EQW => (1, 1),
+            GateType::MUX => (3, 1),
+        }
+    }
+}
+
+/// What a gate computes. Bristol Fashion `EQ` gates assign a constant rather than reading a
+/// wire, so they're kept apart from the gate types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateKind {
+    Op(GateType),
+    Constant(bool),
+}
+
+impl GateKind {
+    /// Name of the gate in a Bristol circuit file
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            GateKind::Op(GateType::AND) => "AND",
+            GateKind::Op(GateType::OR) => "OR",
+            GateKind::Op(GateType::XOR) => "XOR",
+            GateKind::Op(GateType::NOT) => "INV",
+            GateKind::Op(GateType::EQW) => "EQW",
+            GateKind::Op(GateType::NAND) => "NAND",
+            GateKind::Op(GateType::NOR) => "NOR",
+            GateKind::Op(GateType::XNOR) => "XNOR",
+            GateKind::Op(GateType::MUX) => "MUX",
+            GateKind::Constant(_) => "EQ",
+        }
+    }
+
+    pub fn arity(&self) -> (usize, usize) {
+        match self {
+            GateKind::Op(gate_type) => gate_type.arity(),
+            GateKind::Constant(_) => (0, 1),
+        }
+    }
+
+    /// Computes the single output bit of the gate without going through its wires
+    pub fn apply(&self, inputs: &[bool]) -> bool {
+        match self {
+            GateKind::Op(GateType::AND) => inputs[0] && inputs[1],
+            GateKind::Op(GateType::OR) => inputs[0] || inputs[1],
+            GateKind::Op(GateType::XOR) => inputs[0] ^ inputs[1],
+            GateKind::Op(GateType::NOT) => !inputs[0],
+            GateKind::Op(GateType::EQW) => inputs[0],
+            GateKind::Op(GateType::NAND) => !(inputs[0] && inputs[1]),
+            GateKind::Op(GateType::NOR) => !(inputs[0] || inputs[1]),
+            GateKind::Op(GateType::XNOR) => inputs[0] == inputs[1],
+            GateKind::Op(GateType::MUX) => {
+                if inputs[0] {
+                    inputs[2]
+                } else {
+                    inputs[1]
+                }
+            }
+            GateKind::Constant(value) => *value,
+        }
+    }
+
+    /// Bit-sliced version of `apply`, every bit position of the words is an independent
+    /// evaluation of the gate
+    pub fn apply_packed(&self, inputs: &[u64]) -> u64 {
+        match self {
+            GateKind::Op(GateType::AND) => inputs[0] & inputs[1],
+            GateKind::Op(GateType::OR) => inputs[0] | inputs[1],
+            GateKind::Op(GateType::XOR) => inputs[0] ^ inputs[1],
+            GateKind::Op(GateType::NOT) => !inputs[0],
+            GateKind::Op(GateType::EQW) => inputs[0],
+            GateKind::Op(GateType::NAND) => !(inputs[0] & inputs[1]),
+            GateKind::Op(GateType::NOR) => !(inputs[0] | inputs[1]),
+            GateKind::Op(GateType::XNOR) => !(inputs[0] ^ inputs[1]),
+            GateKind::Op(GateType::MUX) => (inputs[0] & inputs[2]) | (!inputs[0] & inputs[1]),
+            GateKind::Constant(value) => 0u64.wrapping_sub(*value as u64),
+        }
+    }
 }
 
 pub struct Gate {
@@ -30,25 +129,48 @@ pub struct Gate {
     pub output_wires: Vec<SafeWire>,
 }
 
-impl Into<GateType> for &str {
-    fn into(self) -> GateType {
-        match self {
-            "AND" => GateType::AND,
-            "OR" => GateType::OR,
-            "XOR" => GateType::XOR,
-            "NOT" => GateType::NOT,
-            _ => panic!("Invalid gate type"),
+/// Returned when a gate name in a circuit file doesn't match any known gate type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGateType(pub String);
+
+impl fmt::Display for UnknownGateType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown gate type '{}'", self.0)
+    }
+}
+
+impl std::error::Error for UnknownGateType {}
+
+impl TryFrom<&str> for GateType {
+    type Error = UnknownGateType;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "AND" => Ok(GateType::AND),
+            "OR" => Ok(GateType::OR),
+            "XOR" => Ok(GateType::XOR),
+            "NOT" | "INV" => Ok(GateType::NOT),
+            "EQW" => Ok(GateType::EQW),
+            "NAND" => Ok(GateType::NAND),
+            "NOR" => Ok(GateType::NOR),
+            "XNOR" => Ok(GateType::XNOR),
+            "MUX" => Ok(GateType::MUX),
+            _ => Err(UnknownGateType(value.to_string())),
         }
     }
 }
 
 impl Gate {
-    pub fn new(gate_type: &str, input_wires: Vec<SafeWire>, output_wires: Vec<SafeWire>) -> Self {
-        Gate {
-            gate_type: gate_type.into(),
+    pub fn new(
+        gate_type: &str,
+        input_wires: Vec<SafeWire>,
+        output_wires: Vec<SafeWire>,
+    ) -> Result<Self, UnknownGateType> {
+        Ok(Gate {
+            gate_type: gate_type.try_into()?,
             input_wires,
             output_wires,
-        }
+        })
     }
 }
 
@@ -67,6 +189,10 @@ impl NotGate {
 }
 
 impl GateTrait for NotGate {
+    fn get_gate_kind(&self) -> GateKind {
+        GateKind::Op(GateType::NOT)
+    }
+
     fn get_input_size(&self) -> usize {
         1
     }
@@ -75,12 +201,12 @@ impl GateTrait for NotGate {
         1
     }
 
-    fn get_input_wires(&mut self) -> &mut Vec<SafeWire> {
-        &mut self.input_wires
+    fn get_input_wires(&self) -> &Vec<SafeWire> {
+        &self.input_wires
     }
 
-    fn get_output_wires(&mut self) -> &mut Vec<SafeWire> {
-        &mut self.output_wires
+    fn get_output_wires(&self) -> &Vec<SafeWire> {
+        &self.output_wires
     }
 
     fn create_response_script(&self, lock_hash: HashValue) -> ScriptBuf {
@@ -126,6 +252,10 @@ impl XorGate {
 }
 
 impl GateTrait for XorGate {
+    fn get_gate_kind(&self) -> GateKind {
+        GateKind::Op(GateType::XOR)
+    }
+
     fn get_input_size(&self) -> usize {
         2
     }
@@ -134,12 +264,12 @@ impl GateTrait for XorGate {
         1
     }
 
-    fn get_input_wires(&mut self) -> &mut Vec<SafeWire> {
-        &mut self.input_wires
+    fn get_input_wires(&self) -> &Vec<SafeWire> {
+        &self.input_wires
     }
 
-    fn get_output_wires(&mut self) -> &mut Vec<SafeWire> {
-        &mut self.output_wires
+    fn get_output_wires(&self) -> &Vec<SafeWire> {
+        &self.output_wires
     }
 
     fn create_response_script(&self, lock_hash: HashValue) -> ScriptBuf {
@@ -187,6 +317,10 @@ impl AndGate {
 }
 
 impl GateTrait for AndGate {
+    fn get_gate_kind(&self) -> GateKind {
+        GateKind::Op(GateType::AND)
+    }
+
     fn get_input_size(&self) -> usize {
         2
     }
@@ -195,12 +329,12 @@ impl GateTrait for AndGate {
         1
     }
 
-    fn get_input_wires(&mut self) -> &mut Wires {
-        &mut self.input_wires
+    fn get_input_wires(&self) -> &Wires {
+        &self.input_wires
     }
 
-    fn get_output_wires(&mut self) -> &mut Wires {
-        &mut self.output_wires
+    fn get_output_wires(&self) -> &Wires {
+        &self.output_wires
     }
 
     fn create_response_script(&self, lock_hash: HashValue) -> ScriptBuf {
@@ -232,6 +366,470 @@ impl GateTrait for AndGate {
     }
 }
 
+pub struct OrGate {
+    pub input_wires: Vec<SafeWire>,
+    pub output_wires: Vec<SafeWire>,
+}
+
+impl OrGate {
+    pub fn new(input_wires: Vec<SafeWire>, output_wires: Vec<SafeWire>) -> Self {
+        OrGate {
+            input_wires,
+            output_wires,
+        }
+    }
+}
+
+impl GateTrait for OrGate {
+    fn get_gate_kind(&self) -> GateKind {
+        GateKind::Op(GateType::OR)
+    }
+
+    fn get_input_size(&self) -> usize {
+        2
+    }
+
+    fn get_output_size(&self) -> usize {
+        1
+    }
+
+    fn get_input_wires(&self) -> &Wires {
+        &self.input_wires
+    }
+
+    fn get_output_wires(&self) -> &Wires {
+        &self.output_wires
+    }
+
+    fn create_response_script(&self, lock_hash: HashValue) -> ScriptBuf {
+        let builder = Builder::new()
+            .push_opcode(OP_SHA256)
+            .push_slice(lock_hash)
+            .push_opcode(OP_EQUALVERIFY);
+        let builder = add_bit_commitment_script(
+            self.output_wires[0].lock().unwrap().get_hash_pair(),
+            builder,
+        )
+        .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[1].lock().unwrap().get_hash_pair(), builder)
+                .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[0].lock().unwrap().get_hash_pair(), builder);
+        builder
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_BOOLOR)
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_EQUALVERIFY)
+            .into_script()
+    }
+
+    fn run_gate_on_inputs(&self, inputs: Vec<bool>) -> Vec<bool> {
+        assert!(inputs.len() == 2);
+        vec![inputs[0] || inputs[1]]
+    }
+}
+
+/// Bristol Fashion `EQ` gate, assigns a constant bit to its output wire
+pub struct EqGate {
+    pub value: bool,
+    pub input_wires: Vec<SafeWire>,
+    pub output_wires: Vec<SafeWire>,
+}
+
+impl EqGate {
+    pub fn new(value: bool, output_wires: Vec<SafeWire>) -> Self {
+        EqGate {
+            value,
+            input_wires: vec![],
+            output_wires,
+        }
+    }
+}
+
+impl GateTrait for EqGate {
+    fn get_gate_kind(&self) -> GateKind {
+        GateKind::Constant(self.value)
+    }
+
+    fn get_input_size(&self) -> usize {
+        0
+    }
+
+    fn get_output_size(&self) -> usize {
+        1
+    }
+
+    fn get_input_wires(&self) -> &Vec<SafeWire> {
+        &self.input_wires
+    }
+
+    fn get_output_wires(&self) -> &Vec<SafeWire> {
+        &self.output_wires
+    }
+
+    fn create_response_script(&self, lock_hash: HashValue) -> ScriptBuf {
+        let builder = Builder::new()
+            .push_opcode(OP_SHA256)
+            .push_slice(lock_hash)
+            .push_opcode(OP_EQUALVERIFY);
+        let builder = add_bit_commitment_script(
+            self.output_wires[0].lock().unwrap().get_hash_pair(),
+            builder,
+        );
+        builder
+            .push_int(self.value as i64)
+            .push_opcode(OP_EQUALVERIFY)
+            .into_script()
+    }
+
+    fn run_gate_on_inputs(&self, inputs: Vec<bool>) -> Vec<bool> {
+        assert!(inputs.is_empty(), "EQ gate takes no input bits");
+        vec![self.value]
+    }
+}
+
+/// Bristol Fashion `EQW` gate, copies its input wire to its output wire
+pub struct EqwGate {
+    pub input_wires: Vec<SafeWire>,
+    pub output_wires: Vec<SafeWire>,
+}
+
+impl EqwGate {
+    pub fn new(input_wires: Vec<SafeWire>, output_wires: Vec<SafeWire>) -> Self {
+        EqwGate {
+            input_wires,
+            output_wires,
+        }
+    }
+}
+
+impl GateTrait for EqwGate {
+    fn get_gate_kind(&self) -> GateKind {
+        GateKind::Op(GateType::EQW)
+    }
+
+    fn get_input_size(&self) -> usize {
+        1
+    }
+
+    fn get_output_size(&self) -> usize {
+        1
+    }
+
+    fn get_input_wires(&self) -> &Vec<SafeWire> {
+        &self.input_wires
+    }
+
+    fn get_output_wires(&self) -> &Vec<SafeWire> {
+        &self.output_wires
+    }
+
+    fn create_response_script(&self, lock_hash: HashValue) -> ScriptBuf {
+        let builder = Builder::new()
+            .push_opcode(OP_SHA256)
+            .push_slice(lock_hash)
+            .push_opcode(OP_EQUALVERIFY);
+        let builder = add_bit_commitment_script(
+            self.output_wires[0].lock().unwrap().get_hash_pair(),
+            builder,
+        )
+        .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[0].lock().unwrap().get_hash_pair(), builder);
+        builder
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_EQUALVERIFY)
+            .into_script()
+    }
+
+    fn run_gate_on_inputs(&self, inputs: Vec<bool>) -> Vec<bool> {
+        assert!(inputs.len() == 1);
+        vec![inputs[0]]
+    }
+}
+
+pub struct NandGate {
+    pub input_wires: Vec<SafeWire>,
+    pub output_wires: Vec<SafeWire>,
+}
+
+impl NandGate {
+    pub fn new(input_wires: Vec<SafeWire>, output_wires: Vec<SafeWire>) -> Self {
+        NandGate {
+            input_wires,
+            output_wires,
+        }
+    }
+}
+
+impl GateTrait for NandGate {
+    fn get_gate_kind(&self) -> GateKind {
+        GateKind::Op(GateType::NAND)
+    }
+
+    fn get_input_size(&self) -> usize {
+        2
+    }
+
+    fn get_output_size(&self) -> usize {
+        1
+    }
+
+    fn get_input_wires(&self) -> &Wires {
+        &self.input_wires
+    }
+
+    fn get_output_wires(&self) -> &Wires {
+        &self.output_wires
+    }
+
+    fn create_response_script(&self, lock_hash: HashValue) -> ScriptBuf {
+        let builder = Builder::new()
+            .push_opcode(OP_SHA256)
+            .push_slice(lock_hash)
+            .push_opcode(OP_EQUALVERIFY);
+        let builder = add_bit_commitment_script(
+            self.output_wires[0].lock().unwrap().get_hash_pair(),
+            builder,
+        )
+        .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[1].lock().unwrap().get_hash_pair(), builder)
+                .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[0].lock().unwrap().get_hash_pair(), builder);
+        builder
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_BOOLAND)
+            .push_opcode(OP_NOT)
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_EQUALVERIFY)
+            .into_script()
+    }
+
+    fn run_gate_on_inputs(&self, inputs: Vec<bool>) -> Vec<bool> {
+        assert!(inputs.len() == 2);
+        vec![!(inputs[0] && inputs[1])]
+    }
+}
+
+pub struct NorGate {
+    pub input_wires: Vec<SafeWire>,
+    pub output_wires: Vec<SafeWire>,
+}
+
+impl NorGate {
+    pub fn new(input_wires: Vec<SafeWire>, output_wires: Vec<SafeWire>) -> Self {
+        NorGate {
+            input_wires,
+            output_wires,
+        }
+    }
+}
+
+impl GateTrait for NorGate {
+    fn get_gate_kind(&self) -> GateKind {
+        GateKind::Op(GateType::NOR)
+    }
+
+    fn get_input_size(&self) -> usize {
+        2
+    }
+
+    fn get_output_size(&self) -> usize {
+        1
+    }
+
+    fn get_input_wires(&self) -> &Wires {
+        &self.input_wires
+    }
+
+    fn get_output_wires(&self) -> &Wires {
+        &self.output_wires
+    }
+
+    fn create_response_script(&self, lock_hash: HashValue) -> ScriptBuf {
+        let builder = Builder::new()
+            .push_opcode(OP_SHA256)
+            .push_slice(lock_hash)
+            .push_opcode(OP_EQUALVERIFY);
+        let builder = add_bit_commitment_script(
+            self.output_wires[0].lock().unwrap().get_hash_pair(),
+            builder,
+        )
+        .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[1].lock().unwrap().get_hash_pair(), builder)
+                .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[0].lock().unwrap().get_hash_pair(), builder);
+        builder
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_BOOLOR)
+            .push_opcode(OP_NOT)
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_EQUALVERIFY)
+            .into_script()
+    }
+
+    fn run_gate_on_inputs(&self, inputs: Vec<bool>) -> Vec<bool> {
+        assert!(inputs.len() == 2);
+        vec![!(inputs[0] || inputs[1])]
+    }
+}
+
+pub struct XnorGate {
+    pub input_wires: Vec<SafeWire>,
+    pub output_wires: Vec<SafeWire>,
+}
+
+impl XnorGate {
+    pub fn new(input_wires: Vec<SafeWire>, output_wires: Vec<SafeWire>) -> Self {
+        XnorGate {
+            input_wires,
+            output_wires,
+        }
+    }
+}
+
+impl GateTrait for XnorGate {
+    fn get_gate_kind(&self) -> GateKind {
+        GateKind::Op(GateType::XNOR)
+    }
+
+    fn get_input_size(&self) -> usize {
+        2
+    }
+
+    fn get_output_size(&self) -> usize {
+        1
+    }
+
+    fn get_input_wires(&self) -> &Wires {
+        &self.input_wires
+    }
+
+    fn get_output_wires(&self) -> &Wires {
+        &self.output_wires
+    }
+
+    fn create_response_script(&self, lock_hash: HashValue) -> ScriptBuf {
+        let builder = Builder::new()
+            .push_opcode(OP_SHA256)
+            .push_slice(lock_hash)
+            .push_opcode(OP_EQUALVERIFY);
+        let builder = add_bit_commitment_script(
+            self.output_wires[0].lock().unwrap().get_hash_pair(),
+            builder,
+        )
+        .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[1].lock().unwrap().get_hash_pair(), builder)
+                .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[0].lock().unwrap().get_hash_pair(), builder);
+        builder
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_NUMEQUAL)
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_EQUALVERIFY)
+            .into_script()
+    }
+
+    fn run_gate_on_inputs(&self, inputs: Vec<bool>) -> Vec<bool> {
+        assert!(inputs.len() == 2);
+        vec![inputs[0] == inputs[1]]
+    }
+}
+
+/// Three input multiplexer, outputs its third input when the first is set and its second
+/// otherwise
+pub struct MuxGate {
+    pub input_wires: Vec<SafeWire>,
+    pub output_wires: Vec<SafeWire>,
+}
+
+impl MuxGate {
+    pub fn new(input_wires: Vec<SafeWire>, output_wires: Vec<SafeWire>) -> Self {
+        MuxGate {
+            input_wires,
+            output_wires,
+        }
+    }
+}
+
+impl GateTrait for MuxGate {
+    fn get_gate_kind(&self) -> GateKind {
+        GateKind::Op(GateType::MUX)
+    }
+
+    fn get_input_size(&self) -> usize {
+        3
+    }
+
+    fn get_output_size(&self) -> usize {
+        1
+    }
+
+    fn get_input_wires(&self) -> &Wires {
+        &self.input_wires
+    }
+
+    fn get_output_wires(&self) -> &Wires {
+        &self.output_wires
+    }
+
+    fn create_response_script(&self, lock_hash: HashValue) -> ScriptBuf {
+        let builder = Builder::new()
+            .push_opcode(OP_SHA256)
+            .push_slice(lock_hash)
+            .push_opcode(OP_EQUALVERIFY);
+        let builder = add_bit_commitment_script(
+            self.output_wires[0].lock().unwrap().get_hash_pair(),
+            builder,
+        )
+        .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[2].lock().unwrap().get_hash_pair(), builder)
+                .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[1].lock().unwrap().get_hash_pair(), builder)
+                .push_opcode(OP_TOALTSTACK);
+        let builder =
+            add_bit_commitment_script(self.input_wires[0].lock().unwrap().get_hash_pair(), builder);
+        // Stack is select a b, keep b when select is set and a otherwise
+        builder
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_ROT)
+            .push_opcode(OP_IF)
+            .push_opcode(OP_NIP)
+            .push_opcode(OP_ELSE)
+            .push_opcode(OP_DROP)
+            .push_opcode(OP_ENDIF)
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_EQUALVERIFY)
+            .into_script()
+    }
+
+    fn run_gate_on_inputs(&self, inputs: Vec<bool>) -> Vec<bool> {
+        assert!(inputs.len() == 3);
+        vec![if inputs[0] { inputs[2] } else { inputs[1] }]
+    }
+}
+
+pub fn create_gate_of_kind(
+    kind: GateKind,
+    input_wires: Vec<SafeWire>,
+    output_wires: Vec<SafeWire>,
+) -> Box<dyn GateTrait> {
+    match kind {
+        GateKind::Op(gate_type) => create_gate(gate_type, input_wires, output_wires),
+        GateKind::Constant(value) => Box::new(EqGate::new(value, output_wires)),
+    }
+}
+
 pub fn create_gate(
     gate_type: GateType,
     input_wires: Vec<SafeWire>,
@@ -239,8 +837,214 @@ pub fn create_gate(
 ) -> Box<dyn GateTrait> {
     match gate_type {
         GateType::AND => Box::new(AndGate::new(input_wires, output_wires)),
-        GateType::OR => panic!("OR gate not implemented"),
+        GateType::OR => Box::new(OrGate::new(input_wires, output_wires)),
         GateType::XOR => Box::new(XorGate::new(input_wires, output_wires)),
         GateType::NOT => Box::new(NotGate::new(input_wires, output_wires)),
+        GateType::EQW => Box::new(EqwGate::new(input_wires, output_wires)),
+        GateType::NAND => Box::new(NandGate::new(input_wires, output_wires)),
+        GateType::NOR => Box::new(NorGate::new(input_wires, output_wires)),
+        GateType::XNOR => Box::new(XnorGate::new(input_wires, output_wires)),
+        GateType::MUX => Box::new(MuxGate::new(input_wires, output_wires)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bitcoin::{
+        hashes::{sha256, Hash},
+        opcodes::all::{OP_BOOLAND, OP_BOOLOR, OP_EQUALVERIFY, OP_VERIFY},
+    };
+
+    use crate::{
+        circuit::wire::{PreimageValue, Wire},
+        traits::gate::GateTrait,
+        transactions::interpreter::{execute_script, ScriptError},
+    };
+
+    use super::{
+        create_gate_of_kind, AndGate, GateKind, GateType, MuxGate, NandGate, NorGate, OrGate,
+        SafeWire, XnorGate, XorGate,
+    };
+
+    const INPUTS: [(bool, bool); 4] = [(false, false), (false, true), (true, false), (true, true)];
+
+    fn wires(indexes: &[usize]) -> Vec<SafeWire> {
+        indexes
+            .iter()
+            .map(|i| Arc::new(Mutex::new(Wire::new(*i, &[0; 32]))))
+            .collect()
+    }
+
+    #[test]
+    fn test_and_gate() {
+        let mut gate = AndGate::new(wires(&[0, 1]), wires(&[2]));
+        for (a, b) in INPUTS {
+            assert_eq!(gate.run_gate_on_inputs(vec![a, b]), vec![a && b]);
+
+            gate.set_input_bits(vec![a, b]);
+            gate.evaluate();
+            assert_eq!(gate.get_output_bits(), vec![a && b]);
+        }
+    }
+
+    #[test]
+    fn test_xor_gate() {
+        let mut gate = XorGate::new(wires(&[0, 1]), wires(&[2]));
+        for (a, b) in INPUTS {
+            assert_eq!(gate.run_gate_on_inputs(vec![a, b]), vec![a ^ b]);
+
+            gate.set_input_bits(vec![a, b]);
+            gate.evaluate();
+            assert_eq!(gate.get_output_bits(), vec![a ^ b]);
+        }
+    }
+
+    #[test]
+    fn test_or_gate() {
+        let mut gate = OrGate::new(wires(&[0, 1]), wires(&[2]));
+        for (a, b) in INPUTS {
+            assert_eq!(gate.run_gate_on_inputs(vec![a, b]), vec![a || b]);
+
+            gate.set_input_bits(vec![a, b]);
+            gate.evaluate();
+            assert_eq!(gate.get_output_bits(), vec![a || b]);
+        }
+    }
+
+    #[test]
+    fn test_or_response_script_enforces_or() {
+        let or_gate = OrGate::new(wires(&[0, 1]), wires(&[2]));
+        let and_gate = AndGate::new(wires(&[0, 1]), wires(&[2]));
+
+        let or_script = or_gate.create_response_script([0; 32]);
+        let and_script = and_gate.create_response_script([0; 32]);
+
+        // The OR script only differs from the AND script by the opcode combining the inputs
+        assert_eq!(or_script.len(), and_script.len());
+        assert!(or_script
+            .instructions()
+            .any(|i| i.unwrap().opcode() == Some(OP_BOOLOR)));
+        assert!(!or_script
+            .instructions()
+            .any(|i| i.unwrap().opcode() == Some(OP_BOOLAND)));
+    }
+
+    #[test]
+    fn test_inverted_gates() {
+        let mut nand = NandGate::new(wires(&[0, 1]), wires(&[2]));
+        let mut nor = NorGate::new(wires(&[0, 1]), wires(&[2]));
+        let mut xnor = XnorGate::new(wires(&[0, 1]), wires(&[2]));
+        for (a, b) in INPUTS {
+            assert_eq!(nand.run_gate_on_inputs(vec![a, b]), vec![!(a && b)]);
+            assert_eq!(nor.run_gate_on_inputs(vec![a, b]), vec![!(a || b)]);
+            assert_eq!(xnor.run_gate_on_inputs(vec![a, b]), vec![a == b]);
+
+            for gate in [
+                &mut nand as &mut dyn GateTrait,
+                &mut nor as &mut dyn GateTrait,
+                &mut xnor as &mut dyn GateTrait,
+            ] {
+                gate.set_input_bits(vec![a, b]);
+                gate.evaluate();
+                assert_eq!(gate.get_output_bits(), gate.run_gate_on_inputs(vec![a, b]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_mux_gate() {
+        let mut gate = MuxGate::new(wires(&[0, 1, 2]), wires(&[3]));
+        for select in [false, true] {
+            for (a, b) in INPUTS {
+                let expected = if select { b } else { a };
+                assert_eq!(gate.run_gate_on_inputs(vec![select, a, b]), vec![expected]);
+
+                gate.set_input_bits(vec![select, a, b]);
+                gate.evaluate();
+                assert_eq!(gate.get_output_bits(), vec![expected]);
+            }
+        }
+    }
+
+    fn witness_items(witness: Vec<PreimageValue>) -> Vec<Vec<u8>> {
+        witness.iter().map(|item| item.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_response_scripts_exhaustively() {
+        let hashlock = [9; 32];
+        let lock_hash = sha256::Hash::hash(&hashlock).to_byte_array();
+        let kinds = [
+            GateType::AND,
+            GateType::OR,
+            GateType::XOR,
+            GateType::NOT,
+            GateType::EQW,
+            GateType::NAND,
+            GateType::NOR,
+            GateType::XNOR,
+            GateType::MUX,
+        ]
+        .map(GateKind::Op)
+        .into_iter()
+        .chain([GateKind::Constant(false), GateKind::Constant(true)]);
+
+        for kind in kinds {
+            let (num_inputs, _) = kind.arity();
+            let input_indexes = (0..num_inputs).collect::<Vec<usize>>();
+            let mut gate = create_gate_of_kind(kind, wires(&input_indexes), wires(&[num_inputs]));
+            let script = gate.create_response_script(lock_hash);
+
+            for combination in 0..1 << num_inputs {
+                let inputs = (0..num_inputs)
+                    .map(|i| combination >> i & 1 == 1)
+                    .collect::<Vec<bool>>();
+                let output = gate.run_gate_on_inputs(inputs.clone())[0];
+                gate.set_input_bits(inputs.clone());
+
+                // Only the output the gate actually computes is accepted
+                for claimed in [false, true] {
+                    gate.set_output_bits(vec![claimed]);
+                    let witness = witness_items(gate.create_response_witness(hashlock));
+                    let expected = if claimed == output {
+                        Ok(vec![])
+                    } else {
+                        Err(ScriptError::VerifyFailed(OP_EQUALVERIFY))
+                    };
+                    assert_eq!(
+                        execute_script(&script, witness, None),
+                        expected,
+                        "{} with inputs {:?} claiming {}",
+                        kind.get_name(),
+                        inputs,
+                        claimed
+                    );
+                }
+
+                gate.set_output_bits(vec![output]);
+                let honest = witness_items(gate.create_response_witness(hashlock));
+                let wrong_hashlock = witness_items(gate.create_response_witness([8; 32]));
+                assert_eq!(
+                    execute_script(&script, wrong_hashlock, None),
+                    Err(ScriptError::VerifyFailed(OP_EQUALVERIFY))
+                );
+                // Every preimage is checked against its own wire's hashes
+                for position in 0..=num_inputs {
+                    let mut forged = honest.clone();
+                    forged[position] = Wire::new(99, &[0; 32])
+                        .preimages
+                        .unwrap()
+                        .zero
+                        .unwrap()
+                        .to_vec();
+                    assert_eq!(
+                        execute_script(&script, forged, None),
+                        Err(ScriptError::VerifyFailed(OP_VERIFY))
+                    );
+                }
+            }
+        }
     }
 }
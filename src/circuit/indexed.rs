@@ -0,0 +1,308 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    iter::zip,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    traits::gate::GateTrait,
+    utils::conversions::{
+        outputs_to_values, values_to_inputs, CircuitValue, ConversionError, Endianness,
+    },
+};
+
+use super::{
+    commitment::CircuitCommitment,
+    error::CircuitParseError,
+    gate::{create_gate_of_kind, GateKind, SafeWire},
+    parser::{parse_gate, parse_header, LineTokens},
+    wire::Wire,
+    BristolFormat,
+};
+
+pub type WireId = usize;
+
+/// A gate that refers to its wires by index rather than holding them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedGate {
+    pub kind: GateKind,
+    pub input_wires: Vec<WireId>,
+    pub output_wires: Vec<WireId>,
+}
+
+impl IndexedGate {
+    /// Reads the gate's inputs from `bits` and writes its output back into it
+    pub fn evaluate(&self, bits: &mut [bool]) {
+        let inputs = self
+            .input_wires
+            .iter()
+            .map(|wire| bits[*wire])
+            .collect::<Vec<bool>>();
+        bits[self.output_wires[0]] = self.kind.apply(&inputs);
+    }
+}
+
+/// Index based representation of a circuit. The structure is kept apart from the wire
+/// commitments, so large circuits can be loaded and evaluated over a flat bit vector without a
+/// mutex per wire. `BristolCircuit` is built from this when the wire hashes are needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Circuit {
+    pub gates: Vec<IndexedGate>,
+    pub num_wires: usize,
+    pub input_wire_sizes: Vec<usize>,
+    pub output_wire_sizes: Vec<usize>,
+    pub format: BristolFormat,
+}
+
+impl Circuit {
+    pub fn from_bristol(path: &str) -> Result<Self, CircuitParseError> {
+        let file = File::open(path)?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, CircuitParseError> {
+        let mut lines = reader
+            .lines()
+            .enumerate()
+            .map(|(i, line)| line.map(|line| (i + 1, line)))
+            .peekable();
+        let header = parse_header(&mut lines)?;
+
+        let mut gates: Vec<IndexedGate> = Vec::new();
+        let mut num_gate_lines = 0;
+        let mut last_line = 0;
+
+        for line in lines {
+            let (line_number, line) = line?;
+            last_line = line_number;
+            if !line.trim().is_empty() {
+                gates.extend(parse_gate(
+                    &mut LineTokens::new(line_number, &line),
+                    header.num_wires,
+                )?);
+                num_gate_lines += 1;
+            }
+        }
+
+        if num_gate_lines != header.num_gates {
+            return Err(CircuitParseError::GateCountMismatch {
+                line: last_line,
+                expected: header.num_gates,
+                found: num_gate_lines,
+            });
+        }
+
+        // A wire that is neither an input, an output nor referenced by a gate can never carry a
+        // value, so a header declaring more wires than that is forged. Rejecting it here keeps
+        // the caller from allocating or deriving preimages for an arbitrary number of wires.
+        let referenced = gates
+            .iter()
+            .map(|gate| gate.input_wires.len() + gate.output_wires.len());
+        let max_wires = header
+            .input_wire_sizes
+            .iter()
+            .chain(&header.output_wire_sizes)
+            .copied()
+            .chain(referenced)
+            .fold(0usize, |total, n| total.saturating_add(n));
+        if header.num_wires > max_wires {
+            return Err(CircuitParseError::WireCountMismatch {
+                line: 1,
+                column: 1,
+                expected: header.num_wires,
+                found: max_wires,
+            });
+        }
+
+        Ok(Circuit {
+            gates,
+            num_wires: header.num_wires,
+            input_wire_sizes: header.input_wire_sizes,
+            output_wire_sizes: header.output_wire_sizes,
+            format: header.format,
+        })
+    }
+
+    /// Index of the first output wire, the outputs are always the last wires of the circuit
+    pub fn output_start(&self) -> WireId {
+        self.num_wires - self.output_wire_sizes.iter().sum::<usize>()
+    }
+
+    /// Runs the circuit on the concatenated input bits, returning the value of every wire
+    pub fn evaluate_wires(&self, input_bits: &[bool]) -> Vec<bool> {
+        let mut bits = vec![false; self.num_wires];
+        bits[..input_bits.len()].copy_from_slice(input_bits);
+        for gate in self.gates.iter() {
+            gate.evaluate(&mut bits);
+        }
+        bits
+    }
+
+    /// Checks the inputs against the declared input sizes and concatenates them in wire order
+    pub fn combine_inputs(&self, inputs: &[Vec<bool>]) -> Vec<bool> {
+        assert_eq!(
+            inputs.len(),
+            self.input_wire_sizes.len(),
+            "wrong number of inputs"
+        );
+        for (a, b) in zip(inputs, self.input_wire_sizes.iter()) {
+            assert_eq!(
+                a.len(),
+                *b,
+                "input lengths do not match for one of the inputs"
+            );
+        }
+        inputs.concat()
+    }
+
+    /// Splits the bits of the output wires into the declared outputs
+    pub fn split_outputs(&self, output_bits: &[bool]) -> Vec<Vec<bool>> {
+        let mut output_index = 0;
+        let mut output = Vec::new();
+        for os in self.output_wire_sizes.iter() {
+            output.push(output_bits[output_index..output_index + os].to_vec());
+            output_index += os;
+        }
+        output
+    }
+
+    pub fn evaluate(&self, inputs: Vec<Vec<bool>>) -> Vec<Vec<bool>> {
+        let bits = self.evaluate_wires(&self.combine_inputs(&inputs));
+        self.split_outputs(&bits[self.output_start()..])
+    }
+
+    /// Evaluates the circuit on one typed value per declared input, reading every output as a
+    /// `T`
+    pub fn evaluate_values<T: CircuitValue>(
+        &self,
+        inputs: &[&dyn CircuitValue],
+        endianness: Endianness,
+    ) -> Result<Vec<T>, ConversionError> {
+        let inputs = values_to_inputs(inputs, &self.input_wire_sizes, endianness)?;
+        outputs_to_values(&self.evaluate(inputs), endianness)
+    }
+
+    /// Builds the `GateTrait` implementation of a single gate from the public wire hashes, so
+    /// its response script can be generated without constructing every wire of the circuit
+    pub fn create_script_gate(
+        &self,
+        gate: usize,
+        commitment: &CircuitCommitment,
+    ) -> Box<dyn GateTrait> {
+        let to_wires = |wires: &Vec<WireId>| {
+            wires
+                .iter()
+                .map(|wire| {
+                    Arc::new(Mutex::new(Wire::from_hashes(
+                        *wire,
+                        commitment.wire_hashes[*wire],
+                    )))
+                })
+                .collect::<Vec<SafeWire>>()
+        };
+        let gate = &self.gates[gate];
+        create_gate_of_kind(
+            gate.kind,
+            to_wires(&gate.input_wires),
+            to_wires(&gate.output_wires),
+        )
+    }
+}
+
+impl FromStr for Circuit {
+    type Err = CircuitParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_reader(s.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        circuit::{tests::TEST_SECRET, BristolCircuit},
+        utils::conversions::{bool_array_to_number, number_to_bool_array},
+    };
+
+    use super::Circuit;
+
+    #[test]
+    fn test_indexed_add_circuit() {
+        let circuit = Circuit::from_bristol("circuits/add.txt").unwrap();
+        assert_eq!(circuit.gates.len(), 376);
+        assert_eq!(circuit.num_wires, 504);
+
+        let b1 = number_to_bool_array(633, 64);
+        let b2 = number_to_bool_array(300, 64);
+        let o = circuit.evaluate(vec![b1, b2]);
+        assert_eq!(bool_array_to_number(o[0].clone()), 933);
+    }
+
+    #[test]
+    fn test_matches_bristol_circuit() {
+        let text =
+            "4 9\n2 2 2\n1 3\n\n4 2 0 1 2 3 4 5 MAND\n1 1 4 6 INV\n1 1 5 7 EQW\n1 1 1 8 EQ\n";
+        let circuit: Circuit = text.parse().unwrap();
+        let mut bristol_circuit = BristolCircuit::from_circuit(&circuit, &TEST_SECRET).unwrap();
+
+        for a in 0..4 {
+            for b in 0..4 {
+                let inputs = vec![number_to_bool_array(a, 2), number_to_bool_array(b, 2)];
+                assert_eq!(
+                    circuit.evaluate(inputs.clone()),
+                    bristol_circuit.evaluate(inputs)
+                );
+            }
+        }
+        assert_eq!(bristol_circuit.to_circuit(), circuit);
+    }
+
+    #[test]
+    fn test_extended_gate_set() {
+        let text = "10 14\n2 2 2\n1 4\n\n0 1 4 CONST0\n0 1 5 CONST1\n2 1 0 2 6 NAND\n\
+                    2 1 1 3 7 NOR\n3 1 0 4 5 8 MUX\n2 1 0 3 10 XNOR\n3 1 1 6 7 11 MUX\n\
+                    2 1 8 2 12 XNOR\n2 1 6 5 9 NAND\n2 1 9 7 13 NOR\n";
+        let circuit: Circuit = text.parse().unwrap();
+        let mut bristol_circuit = BristolCircuit::from_circuit(&circuit, &TEST_SECRET).unwrap();
+
+        for a in 0..4 {
+            for b in 0..4 {
+                let (a0, a1, b0, b1) = (a & 1 == 1, a & 2 == 2, b & 1 == 1, b & 2 == 2);
+                let nand = !(a0 && b0);
+                let nor = !(a1 || b1);
+                let expected = vec![
+                    a0 == b1,
+                    if a1 { nor } else { nand },
+                    a0 == b0,
+                    nand && !nor,
+                ];
+                let inputs = vec![number_to_bool_array(a, 2), number_to_bool_array(b, 2)];
+                assert_eq!(circuit.evaluate(inputs.clone()), vec![expected.clone()]);
+                assert_eq!(bristol_circuit.evaluate(inputs.clone()), vec![expected]);
+                assert_eq!(
+                    circuit.evaluate_batch(std::slice::from_ref(&inputs), 1),
+                    vec![circuit.evaluate(inputs)]
+                );
+            }
+        }
+        assert_eq!(bristol_circuit.to_circuit(), circuit);
+    }
+
+    #[test]
+    fn test_script_gates_match_bristol_circuit() {
+        let bristol_circuit =
+            BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
+        let circuit = bristol_circuit.to_circuit();
+        let commitment = bristol_circuit.commitment();
+
+        for (i, gate) in bristol_circuit.gates.iter().enumerate() {
+            let script_gate = circuit.create_script_gate(i, &commitment);
+            assert_eq!(
+                script_gate.create_response_script([i as u8; 32]),
+                gate.create_response_script([i as u8; 32])
+            );
+        }
+    }
+}
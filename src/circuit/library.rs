@@ -0,0 +1,318 @@
+use super::{
+    builder::CircuitBuilder,
+    commitment::WireSource,
+    error::CircuitParseError,
+    indexed::{Circuit, WireId},
+    BristolCircuit,
+};
+
+/// Names of the circuits bundled with the crate. Integer circuits take their operands least
+/// significant bit first, the SHA-256 circuits take and return byte strings most significant
+/// bit first, i.e. `Endianness::Little` and `Endianness::Big` respectively.
+pub const STANDARD_CIRCUITS: &[&str] = &[
+    "add32",
+    "add64",
+    "sub32",
+    "sub64",
+    "mul32",
+    "mul64",
+    "lt32",
+    "lt64",
+    "eq32",
+    "eq64",
+    "sha256_compression",
+    "sha256_preimage",
+];
+
+const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Builds the standard circuit called `name`, see `STANDARD_CIRCUITS`
+pub fn standard_circuit(name: &str) -> Option<Circuit> {
+    let (op, bits) = match name {
+        "sha256_compression" => return Some(sha256_compression_circuit()),
+        "sha256_preimage" => return Some(sha256_preimage_circuit()),
+        _ => {
+            if let Some(op) = name.strip_suffix("32") {
+                (op, 32)
+            } else {
+                (name.strip_suffix("64")?, 64)
+            }
+        }
+    };
+
+    let mut builder = CircuitBuilder::new();
+    let a = builder.input(bits);
+    let b = builder.input(bits);
+    let output = match op {
+        "add" => builder.add(&a, &b),
+        "sub" => builder.sub(&a, &b),
+        "mul" => builder.mul(&a, &b),
+        "lt" => vec![builder.less_than(&a, &b)],
+        "eq" => vec![builder.equal(&a, &b)],
+        _ => return None,
+    };
+    builder.output(&output);
+    Some(builder.build())
+}
+
+impl BristolCircuit {
+    /// Loads one of the circuits bundled with the crate by name
+    pub fn from_library<'a>(
+        name: &str,
+        wire_source: impl Into<WireSource<'a>>,
+    ) -> Result<Self, CircuitParseError> {
+        let circuit = standard_circuit(name).ok_or_else(|| CircuitParseError::UnknownCircuit {
+            name: name.to_string(),
+        })?;
+        Self::from_circuit(&circuit, wire_source)
+    }
+}
+
+/// Splits a byte string, written most significant bit first, into 32-bit builder words
+fn be_words(bits: &[WireId]) -> Vec<Vec<WireId>> {
+    bits.chunks(32)
+        .map(|word| word.iter().rev().copied().collect())
+        .collect()
+}
+
+/// Inverse of `be_words`
+fn be_bits(words: &[Vec<WireId>]) -> Vec<WireId> {
+    words
+        .iter()
+        .flat_map(|word| word.iter().rev().copied())
+        .collect()
+}
+
+fn big_sigma(builder: &mut CircuitBuilder, x: &[WireId], r: [usize; 3]) -> Vec<WireId> {
+    let a = builder.rotate_right(x, r[0]);
+    let b = builder.rotate_right(x, r[1]);
+    let c = builder.rotate_right(x, r[2]);
+    let ab = builder.xor_word(&a, &b);
+    builder.xor_word(&ab, &c)
+}
+
+fn small_sigma(builder: &mut CircuitBuilder, x: &[WireId], r: [usize; 3]) -> Vec<WireId> {
+    let a = builder.rotate_right(x, r[0]);
+    let b = builder.rotate_right(x, r[1]);
+    let c = builder.shift_right(x, r[2]);
+    let ab = builder.xor_word(&a, &b);
+    builder.xor_word(&ab, &c)
+}
+
+/// The SHA-256 compression function over 8 state words and 16 message words, returning the
+/// next state
+pub fn sha256_compress(
+    builder: &mut CircuitBuilder,
+    state: &[Vec<WireId>],
+    block: &[Vec<WireId>],
+) -> Vec<Vec<WireId>> {
+    assert_eq!(state.len(), 8, "SHA-256 state has 8 words");
+    assert_eq!(block.len(), 16, "SHA-256 block has 16 words");
+
+    let mut w = block.to_vec();
+    for i in 16..64 {
+        let s0 = small_sigma(builder, &w[i - 15], [7, 18, 3]);
+        let s1 = small_sigma(builder, &w[i - 2], [17, 19, 10]);
+        let sum = builder.add(&w[i - 16], &s0);
+        let sum = builder.add(&sum, &w[i - 7]);
+        w.push(builder.add(&sum, &s1));
+    }
+
+    let mut v = state.to_vec();
+    for (i, w) in w.iter().enumerate() {
+        let [a, b, c, d, e, f, g, h] = [0, 1, 2, 3, 4, 5, 6, 7].map(|j| v[j].clone());
+
+        let s1 = big_sigma(builder, &e, [6, 11, 25]);
+        // ch = g ^ (e & (f ^ g))
+        let fg = builder.xor_word(&f, &g);
+        let efg = builder.and_word(&e, &fg);
+        let ch = builder.xor_word(&g, &efg);
+        let k = builder.constant_word(SHA256_K[i] as u128, 32);
+        let temp1 = builder.add(&h, &s1);
+        let temp1 = builder.add(&temp1, &ch);
+        let temp1 = builder.add(&temp1, &k);
+        let temp1 = builder.add(&temp1, w);
+
+        let s0 = big_sigma(builder, &a, [2, 13, 22]);
+        // maj = a ^ ((a ^ b) & (a ^ c))
+        let ab = builder.xor_word(&a, &b);
+        let ac = builder.xor_word(&a, &c);
+        let abac = builder.and_word(&ab, &ac);
+        let maj = builder.xor_word(&a, &abac);
+        let temp2 = builder.add(&s0, &maj);
+
+        let new_e = builder.add(&d, &temp1);
+        let new_a = builder.add(&temp1, &temp2);
+        v = vec![new_a, a, b, c, new_e, e, f, g];
+    }
+
+    state
+        .iter()
+        .zip(v)
+        .map(|(s, v)| builder.add(s, &v))
+        .collect()
+}
+
+/// Inputs are the 32 byte state and the 64 byte block, the output is the next state
+fn sha256_compression_circuit() -> Circuit {
+    let mut builder = CircuitBuilder::new();
+    let state = builder.input(256);
+    let block = builder.input(512);
+    let next = sha256_compress(&mut builder, &be_words(&state), &be_words(&block));
+    builder.output(&be_bits(&next));
+    builder.build()
+}
+
+/// Inputs are a 32 byte preimage and a 32 byte hash, the single output bit is set when the
+/// SHA-256 of the preimage is the hash
+fn sha256_preimage_circuit() -> Circuit {
+    let mut builder = CircuitBuilder::new();
+    let preimage = builder.input(256);
+    let hash = builder.input(256);
+
+    // A 32 byte message fits in one block: the message, a set bit, zeros and the bit length
+    let mut block = be_words(&preimage);
+    block.push(builder.constant_word(0x8000_0000, 32));
+    for _ in 0..6 {
+        block.push(builder.constant_word(0, 32));
+    }
+    block.push(builder.constant_word(256, 32));
+
+    let iv = SHA256_IV
+        .iter()
+        .map(|word| builder.constant_word(*word as u128, 32))
+        .collect::<Vec<Vec<WireId>>>();
+    let digest = be_bits(&sha256_compress(&mut builder, &iv, &block));
+    let matches = builder.equal(&digest, &hash);
+    builder.output(&[matches]);
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::{sha256, Hash};
+
+    use crate::{
+        circuit::{error::CircuitParseError, tests::TEST_SECRET, BristolCircuit},
+        utils::conversions::Endianness,
+    };
+
+    use super::{standard_circuit, SHA256_IV, STANDARD_CIRCUITS};
+
+    fn load(name: &str) -> BristolCircuit {
+        BristolCircuit::from_library(name, &TEST_SECRET).unwrap()
+    }
+
+    const OPERANDS: [u64; 6] = [0, 1, 633, 0xffff_ffff, 0x8000_0000_0000_0001, u64::MAX];
+
+    #[test]
+    fn test_all_circuits_load() {
+        for name in STANDARD_CIRCUITS {
+            assert!(standard_circuit(name).is_some(), "{}", name);
+        }
+        assert!(matches!(
+            BristolCircuit::from_library("div32", &TEST_SECRET),
+            Err(CircuitParseError::UnknownCircuit { .. })
+        ));
+        for name in ["", "a", "aéb", "add16", "é32"] {
+            assert!(standard_circuit(name).is_none(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_64_bit_arithmetic() {
+        let mut add = load("add64");
+        let mut sub = load("sub64");
+        let mut mul = load("mul64");
+        let mut lt = load("lt64");
+        let mut eq = load("eq64");
+        add.validate().unwrap();
+
+        for a in OPERANDS {
+            for b in OPERANDS {
+                let run = |circuit: &mut BristolCircuit| {
+                    circuit
+                        .evaluate_values::<u64>(&[&a, &b], Endianness::Little)
+                        .unwrap()[0]
+                };
+                assert_eq!(run(&mut add), a.wrapping_add(b));
+                assert_eq!(run(&mut sub), a.wrapping_sub(b));
+                assert_eq!(run(&mut mul), a.wrapping_mul(b));
+                assert_eq!(run(&mut lt), (a < b) as u64);
+                assert_eq!(run(&mut eq), (a == b) as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn test_32_bit_arithmetic() {
+        let mut add = load("add32");
+        let mut sub = load("sub32");
+        let mut mul = load("mul32");
+        let mut lt = load("lt32");
+        let mut eq = load("eq32");
+
+        for a in OPERANDS.map(|x| x as u32) {
+            for b in OPERANDS.map(|x| (x >> 3) as u32) {
+                let run = |circuit: &mut BristolCircuit| {
+                    circuit
+                        .evaluate_values::<u32>(&[&a, &b], Endianness::Little)
+                        .unwrap()[0]
+                };
+                assert_eq!(run(&mut add), a.wrapping_add(b));
+                assert_eq!(run(&mut sub), a.wrapping_sub(b));
+                assert_eq!(run(&mut mul), a.wrapping_mul(b));
+                assert_eq!(run(&mut lt), (a < b) as u32);
+                assert_eq!(run(&mut eq), (a == b) as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sha256_compression() {
+        // The single padded block of "abc" compresses to its digest
+        let mut block = [0u8; 64];
+        block[..4].copy_from_slice(&[b'a', b'b', b'c', 0x80]);
+        block[63] = 24;
+        let mut state = [0u8; 32];
+        for (bytes, word) in state.chunks_mut(4).zip(SHA256_IV) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+
+        let mut circuit = load("sha256_compression");
+        let output = circuit
+            .evaluate_values::<[u8; 32]>(&[&state, &block], Endianness::Big)
+            .unwrap();
+        assert_eq!(output[0], sha256::Hash::hash(b"abc").to_byte_array());
+    }
+
+    #[test]
+    fn test_sha256_preimage() {
+        let mut circuit = load("sha256_preimage");
+        let preimage = [7u8; 32];
+        let hash = sha256::Hash::hash(&preimage).to_byte_array();
+
+        let output = circuit
+            .evaluate_values::<u8>(&[&preimage, &hash], Endianness::Big)
+            .unwrap();
+        assert_eq!(output, vec![1]);
+
+        let output = circuit
+            .evaluate_values::<u8>(&[&[8u8; 32], &hash], Endianness::Big)
+            .unwrap();
+        assert_eq!(output, vec![0]);
+    }
+}
@@ -1,105 +1,178 @@
+pub mod builder;
+pub mod commitment;
+pub mod dot;
+pub mod error;
 pub mod gate;
+pub mod indexed;
+pub mod library;
+pub mod optimizer;
+pub mod packed;
+mod parser;
+pub mod stats;
+pub mod trace;
+mod validation;
+pub mod winternitz;
 pub mod wire;
+pub mod word_gate;
 
 use std::{
-    collections::BTreeMap,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, Write},
     iter::zip,
-    sync::{Arc, Mutex},
 };
 
-use gate::{create_gate, Gate, GateType, SafeWire};
-use wire::Wire;
+use commitment::WireSource;
+use error::CircuitParseError;
+use gate::{create_gate_of_kind, SafeWire};
+use indexed::{Circuit, IndexedGate, WireId};
 
-use crate::traits::gate::GateTrait;
+use crate::{
+    traits::gate::{GateTrait, Wires},
+    utils::conversions::{
+        outputs_to_values, values_to_inputs, CircuitValue, ConversionError, Endianness,
+    },
+};
+
+/// The dialect a circuit file is written in. Both use the same gate lines, but legacy Bristol
+/// has a fixed two input, one output header while Bristol Fashion lists any number of inputs
+/// and outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BristolFormat {
+    Legacy,
+    Fashion,
+}
 
 pub struct BristolCircuit {
     pub gates: Vec<Box<dyn GateTrait>>,
     pub wires: Vec<SafeWire>,
     pub input_wire_sizes: Vec<usize>,
     pub output_wire_sizes: Vec<usize>,
+    pub format: BristolFormat,
 }
 
 impl BristolCircuit {
-    pub fn from_bristol(path: &str) -> Self {
-        let mut num_gates: usize = 0;
-        let mut num_wires: usize = 0;
-        let mut wires: Vec<Wire> = Vec::new();
-        let mut gates: Vec<Box<dyn GateTrait>> = Vec::new();
-        let mut input_wire_sizes: Vec<usize> = Vec::new();
-        let mut output_wire_sizes: Vec<usize> = Vec::new();
-        let mut wire_indexes = BTreeMap::new();
-
-        let file = File::open(path).unwrap();
-
-        let lines = BufReader::new(file).lines();
-
-        for (i, line) in lines.enumerate() {
-            if let Ok(line) = line {
-                if i == 0 {
-                    // First line of header contains the number of gates and wires
-                    let mut words = line.split_whitespace();
-                    num_gates = words.next().unwrap().parse().unwrap();
-                    num_wires = words.next().unwrap().parse().unwrap();
-
-                    // Construct the wires
-                    for i in 0..num_wires {
-                        let wire = Wire::new(i, Some(0));
-                        wire_indexes.insert(i, Arc::new(Mutex::new(wire)));
-                    }
-                } else if i == 1 {
-                    // Second line of header contains the number of input wires
-                    let mut values = line.split_whitespace();
-                    for _ in 0..values.next().unwrap().parse().unwrap() {
-                        let x: usize = values.next().unwrap().parse().unwrap();
-                        input_wire_sizes.push(x);
-                    }
-                } else if i == 2 {
-                    // Third line of header contains the number of output wires
-                    let mut values = line.split_whitespace();
-                    for _ in 0..values.next().unwrap().parse().unwrap() {
-                        let x: usize = values.next().unwrap().parse().unwrap();
-                        output_wire_sizes.push(x);
-                    }
-                } else if !line.is_empty() {
-                    let mut values = line.split_whitespace();
-                    let num_inputs = values.next().unwrap().parse().unwrap();
-                    let num_outputs = values.next().unwrap().parse().unwrap();
-
-                    let input_wires: Vec<Arc<Mutex<Wire>>> = (0..num_inputs)
-                        .map(|_| {
-                            let k = values.next().unwrap().parse::<usize>().unwrap();
-                            let x = wire_indexes.get(&k).unwrap().clone();
-                            x
-                        })
-                        .collect();
-
-                    let output_wires: Vec<Arc<Mutex<Wire>>> = (0..num_outputs)
-                        .map(|_| {
-                            let k = values.next().unwrap().parse::<usize>().unwrap();
-                            let x = wire_indexes.get(&k).unwrap().clone();
-                            x
-                        })
-                        .collect();
-
-                    let gate_type: GateType = values.next().unwrap().into();
-
-                    let gate = create_gate(gate_type, input_wires, output_wires);
-                    gates.push(gate);
+    /// Loads a circuit from a file. The prover passes its `WireSecret` so every wire preimage
+    /// is derived from it, while the verifier passes the prover's `CircuitCommitment`.
+    pub fn from_bristol<'a>(
+        path: &str,
+        wire_source: impl Into<WireSource<'a>>,
+    ) -> Result<Self, CircuitParseError> {
+        let file = File::open(path)?;
+        Self::from_reader(BufReader::new(file), wire_source)
+    }
+
+    /// Parses a circuit from any buffered source, e.g. one embedded in the binary or received
+    /// from the other actor
+    pub fn from_reader<'a>(
+        reader: impl BufRead,
+        wire_source: impl Into<WireSource<'a>>,
+    ) -> Result<Self, CircuitParseError> {
+        Self::from_circuit(&Circuit::from_reader(reader)?, wire_source)
+    }
+
+    /// Creates the wires of an index based circuit and the gates holding them
+    pub fn from_circuit<'a>(
+        circuit: &Circuit,
+        wire_source: impl Into<WireSource<'a>>,
+    ) -> Result<Self, CircuitParseError> {
+        let wires = wire_source.into().create_wires(circuit.num_wires)?;
+        let gates = circuit
+            .gates
+            .iter()
+            .map(|gate| {
+                create_gate_of_kind(
+                    gate.kind,
+                    gate.input_wires.iter().map(|i| wires[*i].clone()).collect(),
+                    gate.output_wires
+                        .iter()
+                        .map(|i| wires[*i].clone())
+                        .collect(),
+                )
+            })
+            .collect();
+
+        Ok(BristolCircuit {
+            gates,
+            wires,
+            input_wire_sizes: circuit.input_wire_sizes.clone(),
+            output_wire_sizes: circuit.output_wire_sizes.clone(),
+            format: circuit.format,
+        })
+    }
+
+    /// The index based structure of the circuit, without any of the wire commitments
+    pub fn to_circuit(&self) -> Circuit {
+        let wire_ids = |wires: &Wires| {
+            wires
+                .iter()
+                .map(|wire_arcm| {
+                    wire_arcm
+                        .lock()
+                        .unwrap()
+                        .index
+                        .expect("wire index is not set")
+                })
+                .collect::<Vec<WireId>>()
+        };
+        Circuit {
+            gates: self
+                .gates
+                .iter()
+                .map(|gate| IndexedGate {
+                    kind: gate.get_gate_kind(),
+                    input_wires: wire_ids(gate.get_input_wires()),
+                    output_wires: wire_ids(gate.get_output_wires()),
+                })
+                .collect(),
+            num_wires: self.wires.len(),
+            input_wire_sizes: self.input_wire_sizes.clone(),
+            output_wire_sizes: self.output_wire_sizes.clone(),
+            format: self.format,
+        }
+    }
+
+    /// Serializes the circuit in the same layout `from_bristol` reads, using the circuit's
+    /// dialect for the header
+    pub fn write_bristol(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{} {}", self.gates.len(), self.wires.len())?;
+        match self.format {
+            BristolFormat::Legacy => {
+                if self.input_wire_sizes.len() != 2 || self.output_wire_sizes.len() != 1 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "legacy Bristol circuits must have two inputs and one output",
+                    ));
                 }
+                writeln!(
+                    writer,
+                    "{} {} {}",
+                    self.input_wire_sizes[0], self.input_wire_sizes[1], self.output_wire_sizes[0]
+                )?;
+            }
+            BristolFormat::Fashion => {
+                writeln!(writer, "{}", wire_sizes_line(&self.input_wire_sizes))?;
+                writeln!(writer, "{}", wire_sizes_line(&self.output_wire_sizes))?;
             }
         }
-
-        BristolCircuit {
-            gates,
-            input_wire_sizes,
-            output_wire_sizes,
-            wires: wire_indexes
-                .values()
-                .cloned()
-                .collect::<Vec<Arc<Mutex<Wire>>>>(),
+        writeln!(writer)?;
+        for gate in self.gates.iter() {
+            writeln!(writer, "{}", gate.get_bristol_line())?;
         }
+        Ok(())
+    }
+
+    pub fn to_bristol(&self) -> String {
+        let mut bytes = Vec::new();
+        self.write_bristol(&mut bytes)
+            .expect("writing to a vec can't fail");
+        String::from_utf8(bytes).expect("circuit should be valid utf8")
+    }
+
+    pub fn from_str<'a>(
+        text: &str,
+        wire_source: impl Into<WireSource<'a>>,
+    ) -> Result<Self, CircuitParseError> {
+        Self::from_reader(text.as_bytes(), wire_source)
     }
 
     pub fn evaluate(&mut self, inputs: Vec<Vec<bool>>) -> Vec<Vec<bool>> {
@@ -137,28 +210,197 @@ impl BristolCircuit {
         }
         output
     }
+
+    /// Evaluates the circuit on one typed value per declared input, reading every output as a
+    /// `T`
+    pub fn evaluate_values<T: CircuitValue>(
+        &mut self,
+        inputs: &[&dyn CircuitValue],
+        endianness: Endianness,
+    ) -> Result<Vec<T>, ConversionError> {
+        let inputs = values_to_inputs(inputs, &self.input_wire_sizes, endianness)?;
+        outputs_to_values(&self.evaluate(inputs), endianness)
+    }
+}
+
+fn wire_sizes_line(sizes: &[usize]) -> String {
+    let mut values = vec![sizes.len()];
+    values.extend(sizes);
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{collections::HashSet, io::Cursor};
+
     use crate::{
-        circuit::BristolCircuit,
-        utils::conversions::{bool_array_to_number, number_to_bool_array},
+        circuit::{error::CircuitParseError, BristolCircuit, BristolFormat},
+        utils::conversions::{
+            bool_array_to_number, number_to_bool_array, ConversionError, Endianness,
+        },
     };
 
+    pub const TEST_SECRET: [u8; 32] = [0; 32];
+
+    fn parse(text: &str) -> Result<BristolCircuit, CircuitParseError> {
+        BristolCircuit::from_str(text, &TEST_SECRET)
+    }
+
     #[test]
     fn test_circuit_state() {
-        let circuit = BristolCircuit::from_bristol("circuits/add.txt");
+        let circuit = BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
 
         assert_eq!(circuit.gates.len(), 376);
         assert_eq!(circuit.wires.len(), 504);
         assert_eq!(circuit.input_wire_sizes, vec![64, 64]);
         assert_eq!(circuit.output_wire_sizes, vec![64]);
+        assert_eq!(circuit.format, BristolFormat::Fashion);
+    }
+
+    #[test]
+    fn test_circuit_from_str_and_reader() {
+        let text = "2 5\n2 1 2\n1 1\n\n2 1 0 1 3 AND\n2 1 2 3 4 XOR\n";
+
+        let mut circuit = BristolCircuit::from_str(text, &TEST_SECRET).unwrap();
+        assert_eq!(circuit.gates.len(), 2);
+        assert_eq!(circuit.input_wire_sizes, vec![1, 2]);
+        assert_eq!(
+            circuit.evaluate(vec![vec![true], vec![true, false]]),
+            vec![vec![true]]
+        );
+
+        let mut circuit = BristolCircuit::from_reader(Cursor::new(text), &TEST_SECRET).unwrap();
+        assert_eq!(
+            circuit.evaluate(vec![vec![true], vec![true, true]]),
+            vec![vec![false]]
+        );
+    }
+
+    #[test]
+    fn test_write_bristol_round_trip() {
+        let text = "2 5\n2 1 2\n1 1\n\n2 1 0 1 3 AND\n2 1 2 3 4 XOR\n";
+        let circuit = parse(text).unwrap();
+        assert_eq!(circuit.to_bristol(), text);
+
+        let text = "2 4\n1 1 1\n\n2 1 0 1 2 AND\n1 1 2 3 INV\n";
+        let circuit = parse(text).unwrap();
+        assert_eq!(circuit.to_bristol(), text);
+
+        let text = "2 4\n1 1\n1 2\n\n1 1 1 2 EQ\n1 1 0 3 EQW\n";
+        let circuit = parse(text).unwrap();
+        assert_eq!(circuit.to_bristol(), text);
+    }
+
+    #[test]
+    fn test_write_bristol_add_circuit() {
+        let circuit = BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
+        let mut written = Vec::new();
+        circuit.write_bristol(&mut written).unwrap();
+
+        let mut reloaded = BristolCircuit::from_reader(written.as_slice(), &TEST_SECRET).unwrap();
+        assert_eq!(reloaded.gates.len(), circuit.gates.len());
+        assert_eq!(reloaded.wires.len(), circuit.wires.len());
+        assert_eq!(reloaded.to_bristol().as_bytes(), written.as_slice());
+
+        let b1 = number_to_bool_array(1234, 64);
+        let b2 = number_to_bool_array(4321, 64);
+        let o = reloaded.evaluate(vec![b1, b2]);
+        assert_eq!(bool_array_to_number(o[0].clone()), 5555);
+    }
+
+    #[test]
+    fn test_legacy_circuit() {
+        let mut circuit = parse("2 4\n1 1 1\n\n2 1 0 1 2 AND\n1 1 2 3 INV\n").unwrap();
+
+        assert_eq!(circuit.format, BristolFormat::Legacy);
+        assert_eq!(circuit.input_wire_sizes, vec![1, 1]);
+        assert_eq!(circuit.output_wire_sizes, vec![1]);
+        assert_eq!(
+            circuit.evaluate(vec![vec![true], vec![true]]),
+            vec![vec![false]]
+        );
+        assert_eq!(
+            circuit.evaluate(vec![vec![true], vec![false]]),
+            vec![vec![true]]
+        );
+    }
+
+    #[test]
+    fn test_bristol_fashion_gates() {
+        let mut circuit = parse(
+            "4 9\n2 2 2\n1 3\n\n4 2 0 1 2 3 4 5 MAND\n1 1 4 6 INV\n1 1 5 7 EQW\n1 1 1 8 EQ\n",
+        )
+        .unwrap();
+
+        assert_eq!(circuit.format, BristolFormat::Fashion);
+        // The MAND line is expanded into one AND gate per output
+        assert_eq!(circuit.gates.len(), 5);
+
+        let o = circuit.evaluate(vec![vec![true, true], vec![true, false]]);
+        assert_eq!(o, vec![vec![false, false, true]]);
+
+        let o = circuit.evaluate(vec![vec![false, true], vec![true, true]]);
+        assert_eq!(o, vec![vec![true, true, true]]);
+    }
+
+    #[test]
+    fn test_or_circuit() {
+        let mut circuit = parse("1 3\n2 1 1\n1 1\n\n2 1 0 1 2 OR\n").unwrap();
+
+        assert_eq!(
+            circuit.evaluate(vec![vec![false], vec![false]]),
+            vec![vec![false]]
+        );
+        assert_eq!(
+            circuit.evaluate(vec![vec![false], vec![true]]),
+            vec![vec![true]]
+        );
+        assert_eq!(
+            circuit.evaluate(vec![vec![true], vec![true]]),
+            vec![vec![true]]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_on_bad_eq_constant() {
+        let result = parse("1 3\n1 2\n1 1\n\n1 1 2 2 EQ\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::InvalidNumber {
+                line: 5,
+                column: 5,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_circuit_wires_have_distinct_hashes() {
+        let circuit = BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
+        let mut hashes = HashSet::new();
+        for wire in circuit.wires.iter() {
+            let hash_pair = wire.lock().unwrap().get_hash_pair();
+            assert!(hashes.insert(hash_pair.zero));
+            assert!(hashes.insert(hash_pair.one));
+        }
+
+        // The same secret reproduces the same commitments
+        let reloaded = BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
+        for (a, b) in circuit.wires.iter().zip(reloaded.wires.iter()) {
+            assert_eq!(
+                a.lock().unwrap().get_hash_pair().one,
+                b.lock().unwrap().get_hash_pair().one
+            );
+        }
     }
 
     #[test]
     fn test_add_circuit() {
-        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt");
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
         let a1 = 633;
         let a2 = 300;
         let b1 = number_to_bool_array(a1, 64);
@@ -169,4 +411,162 @@ mod tests {
 
         assert_eq!(output, a1 + a2);
     }
+
+    #[test]
+    fn test_add_circuit_with_values() {
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
+        let a1 = u64::MAX - 5;
+        let a2 = 300u64;
+
+        let o = circuit
+            .evaluate_values::<u64>(&[&a1, &a2], Endianness::Little)
+            .unwrap();
+        assert_eq!(o, vec![a1.wrapping_add(a2)]);
+
+        let result = circuit.evaluate_values::<u32>(&[&a1, &a2], Endianness::Little);
+        assert_eq!(
+            result,
+            Err(ConversionError::TypeTooSmall {
+                size: 64,
+                capacity: 32
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_on_missing_file() {
+        let result = BristolCircuit::from_bristol("circuits/does_not_exist.txt", &TEST_SECRET);
+        assert!(matches!(result, Err(CircuitParseError::Io(_))));
+    }
+
+    #[test]
+    fn test_parse_errors_on_bad_header() {
+        let result = parse("2 five\n2 1 2\n1 1\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::InvalidNumber {
+                line: 1,
+                column: 3,
+                ..
+            })
+        ));
+
+        let result = parse("1 4\n2 1\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::InvalidHeader { line: 2, .. })
+        ));
+
+        let result = parse("1 4\n1 1\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::InvalidHeader { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors_on_wire_index_out_of_range() {
+        let result = parse("1 3\n2 1 1\n1 1\n\n2 1 0 7 2 AND\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::WireIndexOutOfRange {
+                line: 5,
+                column: 7,
+                index: 7,
+                num_wires: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors_on_unknown_gate() {
+        let result = parse("1 3\n2 1 1\n1 1\n\n2 1 0 1 2 FOO\n");
+        match result {
+            Err(CircuitParseError::UnknownGate { line, column, name }) => {
+                assert_eq!((line, column, name.as_str()), (5, 11, "FOO"));
+            }
+            _ => panic!("expected an unknown gate error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors_on_gate_arity_mismatch() {
+        let result = parse("1 3\n2 1 1\n1 1\n\n1 1 0 2 AND\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::GateArityMismatch {
+                line: 5,
+                expected: (2, 1),
+                found: (1, 1),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors_on_count_mismatches() {
+        let result = parse("2 3\n2 1 1\n1 1\n\n2 1 0 1 2 AND\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::GateCountMismatch {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+
+        let result = parse("1 3\n2 2 2\n1 1\n\n2 1 0 1 2 AND\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::WireCountMismatch {
+                expected: 3,
+                found: 5,
+                ..
+            })
+        ));
+
+        // A header can't declare more wires than the inputs, outputs and gates can reference
+        let result = parse("1 1000000000000\n2 1 1\n1 1\n\n2 1 0 1 2 AND\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::WireCountMismatch {
+                expected: 1_000_000_000_000,
+                found: 6,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors_on_overflowing_wire_sizes() {
+        let result = parse("1 10\n2 18446744073709551615 1\n1 1\n\n2 1 0 1 2 AND\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::InvalidHeader { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors_on_overflowing_gate_arity() {
+        let result = parse("1 3\n2 1 1\n1 1\n\n18446744073709551615 1 0 1 2 AND\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::GateArityMismatch {
+                line: 5,
+                column: 30,
+                expected: (2, 1),
+                found: (usize::MAX, 1),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors_on_truncated_gate() {
+        let result = parse("1 3\n2 1 1\n1 1\n\n2 1 0 1\n");
+        assert!(matches!(
+            result,
+            Err(CircuitParseError::UnexpectedEndOfLine { line: 5, .. })
+        ));
+    }
 }
@@ -0,0 +1,434 @@
+use std::{collections::HashMap, fmt};
+
+use super::{
+    builder::CircuitBuilder,
+    commitment::WireSource,
+    error::CircuitParseError,
+    gate::{GateKind, GateType},
+    indexed::{Circuit, WireId},
+    BristolCircuit,
+};
+
+/// Gate and wire counts of a circuit before and after optimization
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizationReport {
+    pub gates_before: usize,
+    pub gates_after: usize,
+    pub wires_before: usize,
+    pub wires_after: usize,
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gates: {} -> {}, wires: {} -> {}",
+            self.gates_before, self.gates_after, self.wires_before, self.wires_after
+        )
+    }
+}
+
+/// Value of an original wire in the optimized circuit. NOTs are carried as an inversion flag
+/// until a gate that can't absorb them needs the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Signal {
+    Constant(bool),
+    Wire { wire: WireId, inverted: bool },
+}
+
+impl Signal {
+    fn invert(self, invert: bool) -> Signal {
+        match self {
+            Signal::Constant(value) => Signal::Constant(value ^ invert),
+            Signal::Wire { wire, inverted } => Signal::Wire {
+                wire,
+                inverted: inverted ^ invert,
+            },
+        }
+    }
+}
+
+struct Optimizer {
+    builder: CircuitBuilder,
+    /// Gates already emitted, keyed by their kind and inputs, so equal gates are only built once
+    emitted: HashMap<(GateType, Vec<WireId>), WireId>,
+    /// The gate that produced each emitted wire, so an inverted wire can be built as the
+    /// complementary gate instead of adding a NOT
+    producers: HashMap<WireId, (GateType, Vec<WireId>)>,
+}
+
+impl Optimizer {
+    fn emit(&mut self, gate_type: GateType, mut inputs: Vec<WireId>) -> WireId {
+        // MUX is the only gate whose inputs can't be reordered
+        if gate_type != GateType::MUX {
+            inputs.sort_unstable();
+        }
+        let key = (gate_type, inputs);
+        if let Some(wire) = self.emitted.get(&key) {
+            return *wire;
+        }
+        let inputs = &key.1;
+        let wire = match gate_type {
+            GateType::AND => self.builder.and(inputs[0], inputs[1]),
+            GateType::OR => self.builder.or(inputs[0], inputs[1]),
+            GateType::XOR => self.builder.xor(inputs[0], inputs[1]),
+            GateType::NOT => self.builder.not(inputs[0]),
+            GateType::EQW => inputs[0],
+            GateType::NAND => self.builder.nand(inputs[0], inputs[1]),
+            GateType::NOR => self.builder.nor(inputs[0], inputs[1]),
+            GateType::XNOR => self.builder.xnor(inputs[0], inputs[1]),
+            GateType::MUX => self.builder.mux_gate(inputs[0], inputs[1], inputs[2]),
+        };
+        self.producers.insert(wire, key.clone());
+        self.emitted.insert(key, wire);
+        wire
+    }
+
+    fn materialize(&mut self, signal: Signal) -> WireId {
+        match signal {
+            Signal::Constant(value) => self.builder.constant(value),
+            Signal::Wire {
+                wire,
+                inverted: false,
+            } => wire,
+            Signal::Wire {
+                wire,
+                inverted: true,
+            } => {
+                let complement = match self.producers.get(&wire) {
+                    Some((GateType::NOT, inputs)) => return inputs[0],
+                    Some((gate_type, inputs)) => match gate_type {
+                        GateType::AND => Some((GateType::NAND, inputs.clone())),
+                        GateType::OR => Some((GateType::NOR, inputs.clone())),
+                        GateType::XOR => Some((GateType::XNOR, inputs.clone())),
+                        GateType::NAND => Some((GateType::AND, inputs.clone())),
+                        GateType::NOR => Some((GateType::OR, inputs.clone())),
+                        GateType::XNOR => Some((GateType::XOR, inputs.clone())),
+                        _ => None,
+                    },
+                    None => None,
+                };
+                match complement {
+                    Some((gate_type, inputs)) => self.emit(gate_type, inputs),
+                    None => self.emit(GateType::NOT, vec![wire]),
+                }
+            }
+        }
+    }
+
+    /// `absorbed` tells whether every reader of the output is a NOT gate, in which case a single
+    /// inverted input can be moved past the XOR for free
+    fn xor(&mut self, a: Signal, b: Signal, absorbed: bool) -> Signal {
+        match (a, b) {
+            (Signal::Constant(c), other) | (other, Signal::Constant(c)) => other.invert(c),
+            (
+                Signal::Wire {
+                    wire: a_wire,
+                    inverted: a_inverted,
+                },
+                Signal::Wire {
+                    wire: b_wire,
+                    inverted: b_inverted,
+                },
+            ) => {
+                if a_wire == b_wire {
+                    return Signal::Constant(a_inverted ^ b_inverted);
+                }
+                if a_inverted != b_inverted && !absorbed {
+                    // Moving the NOT to the output would only make a later gate pay for it
+                    let inputs = vec![self.materialize(a), self.materialize(b)];
+                    return Signal::Wire {
+                        wire: self.emit(GateType::XOR, inputs),
+                        inverted: false,
+                    };
+                }
+                // !a ^ b == !(a ^ b), so NOTs on the inputs move to the output
+                Signal::Wire {
+                    wire: self.emit(GateType::XOR, vec![a_wire, b_wire]),
+                    inverted: a_inverted ^ b_inverted,
+                }
+            }
+        }
+    }
+
+    /// AND when `is_or` is false, otherwise OR. The two only differ in which constant absorbs.
+    fn and_or(&mut self, a: Signal, b: Signal, is_or: bool) -> Signal {
+        match (a, b) {
+            (Signal::Constant(c), other) | (other, Signal::Constant(c)) => {
+                if c == is_or {
+                    Signal::Constant(c)
+                } else {
+                    other
+                }
+            }
+            (Signal::Wire { wire: a_wire, .. }, Signal::Wire { wire: b_wire, .. })
+                if a_wire == b_wire =>
+            {
+                if a == b {
+                    a
+                } else {
+                    // a & !a is always 0 and a | !a always 1
+                    Signal::Constant(is_or)
+                }
+            }
+            _ => {
+                let inputs = vec![self.materialize(a), self.materialize(b)];
+                let gate_type = if is_or { GateType::OR } else { GateType::AND };
+                Signal::Wire {
+                    wire: self.emit(gate_type, inputs),
+                    inverted: false,
+                }
+            }
+        }
+    }
+
+    /// `b` if `select` is set, otherwise `a`
+    fn mux(&mut self, select: Signal, a: Signal, b: Signal) -> Signal {
+        match (select, a, b) {
+            (Signal::Constant(select), a, b) => {
+                if select {
+                    b
+                } else {
+                    a
+                }
+            }
+            (_, a, b) if a == b => a,
+            (select, Signal::Constant(a), Signal::Constant(b)) => {
+                // The constants differ, so the output is the select or its inverse
+                select.invert(a && !b)
+            }
+            (select, a, b) => {
+                let inputs = vec![
+                    self.materialize(select),
+                    self.materialize(a),
+                    self.materialize(b),
+                ];
+                Signal::Wire {
+                    wire: self.emit(GateType::MUX, inputs),
+                    inverted: false,
+                }
+            }
+        }
+    }
+}
+
+impl Circuit {
+    /// Returns an equivalent circuit with fewer gates. Constants are propagated, double NOTs
+    /// removed, NOTs folded into XOR gates or their complementary gate, equal gates merged and
+    /// gates that don't contribute to an output dropped. Wires are renumbered, so the result
+    /// needs new commitments. The circuit must pass `BristolCircuit::validate`.
+    pub fn optimize(&self) -> (Circuit, OptimizationReport) {
+        let mut optimizer = Optimizer {
+            builder: CircuitBuilder::new(),
+            emitted: HashMap::new(),
+            producers: HashMap::new(),
+        };
+        let mut signals: Vec<Option<Signal>> = vec![None; self.num_wires];
+        let inputs = self
+            .input_wire_sizes
+            .iter()
+            .flat_map(|size| optimizer.builder.input(*size))
+            .collect::<Vec<WireId>>();
+        for (i, wire) in inputs.into_iter().enumerate() {
+            signals[i] = Some(Signal::Wire {
+                wire,
+                inverted: false,
+            });
+        }
+
+        // Wires only read by NOT gates, an inversion on them cancels out
+        let mut absorbs_inversion = vec![true; self.num_wires];
+        let mut is_read = vec![false; self.num_wires];
+        for gate in self.gates.iter() {
+            for wire in gate.input_wires.iter() {
+                is_read[*wire] = true;
+                absorbs_inversion[*wire] &= gate.kind == GateKind::Op(GateType::NOT);
+            }
+        }
+        for wire in 0..self.num_wires {
+            absorbs_inversion[wire] &= is_read[wire] && wire < self.output_start();
+        }
+
+        for gate in self.gates.iter() {
+            let inputs = gate
+                .input_wires
+                .iter()
+                .map(|wire| signals[*wire].expect("wire is read before it is written"))
+                .collect::<Vec<Signal>>();
+            let signal = match gate.kind {
+                GateKind::Constant(value) => Signal::Constant(value),
+                GateKind::Op(GateType::EQW) => inputs[0],
+                GateKind::Op(GateType::NOT) => inputs[0].invert(true),
+                GateKind::Op(GateType::XOR) => optimizer.xor(
+                    inputs[0],
+                    inputs[1],
+                    absorbs_inversion[gate.output_wires[0]],
+                ),
+                GateKind::Op(GateType::AND) => optimizer.and_or(inputs[0], inputs[1], false),
+                GateKind::Op(GateType::OR) => optimizer.and_or(inputs[0], inputs[1], true),
+                // A single inverted input cancels the XNOR's own inversion, so it's always free
+                GateKind::Op(GateType::XNOR) => {
+                    optimizer.xor(inputs[0], inputs[1], true).invert(true)
+                }
+                GateKind::Op(GateType::NAND) => {
+                    optimizer.and_or(inputs[0], inputs[1], false).invert(true)
+                }
+                GateKind::Op(GateType::NOR) => {
+                    optimizer.and_or(inputs[0], inputs[1], true).invert(true)
+                }
+                GateKind::Op(GateType::MUX) => optimizer.mux(inputs[0], inputs[1], inputs[2]),
+            };
+            signals[gate.output_wires[0]] = Some(signal);
+        }
+
+        let mut output_start = self.output_start();
+        for size in self.output_wire_sizes.iter() {
+            let output = (output_start..output_start + size)
+                .map(|wire| {
+                    let signal = signals[wire].expect("output wire is never written");
+                    optimizer.materialize(signal)
+                })
+                .collect::<Vec<WireId>>();
+            optimizer.builder.output(&output);
+            output_start += size;
+        }
+
+        let mut optimized = optimizer.builder.build();
+        optimized.format = self.format;
+        let report = OptimizationReport {
+            gates_before: self.gates.len(),
+            gates_after: optimized.gates.len(),
+            wires_before: self.num_wires,
+            wires_after: optimized.num_wires,
+        };
+        (optimized, report)
+    }
+}
+
+impl BristolCircuit {
+    /// Optimizes the circuit, see `Circuit::optimize`, and commits to the wires of the result
+    pub fn optimize<'a>(
+        &self,
+        wire_source: impl Into<WireSource<'a>>,
+    ) -> Result<(BristolCircuit, OptimizationReport), CircuitParseError> {
+        let (circuit, report) = self.to_circuit().optimize();
+        Ok((BristolCircuit::from_circuit(&circuit, wire_source)?, report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::key::rand::{self, Rng};
+
+    use crate::{
+        circuit::{
+            indexed::Circuit, library::standard_circuit, tests::TEST_SECRET, BristolCircuit,
+        },
+        utils::conversions::Endianness,
+    };
+
+    fn assert_equivalent(a: &Circuit, b: &Circuit, inputs: Vec<Vec<Vec<bool>>>) {
+        assert_eq!(a.evaluate_batch(&inputs, 1), b.evaluate_batch(&inputs, 1));
+    }
+
+    fn random_inputs(circuit: &Circuit, count: usize) -> Vec<Vec<Vec<bool>>> {
+        let mut rng = rand::thread_rng();
+        (0..count)
+            .map(|_| {
+                circuit
+                    .input_wire_sizes
+                    .iter()
+                    .map(|size| (0..*size).map(|_| rng.gen()).collect())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_small_circuit_passes() {
+        // INV INV is a double NOT, the INV into XOR folds, the second AND is a duplicate of the
+        // first and the OR is dead
+        let text = "9 13\n2 2 2\n1 2\n\n1 1 0 4 INV\n1 1 4 5 INV\n1 1 1 6 INV\n2 1 5 6 7 XOR\n\
+                    2 1 0 1 8 AND\n2 1 1 0 9 AND\n2 1 2 3 10 OR\n2 1 8 9 11 XOR\n2 1 7 11 12 AND\n";
+        let circuit: Circuit = text.parse().unwrap();
+        let (optimized, report) = circuit.optimize();
+
+        assert_eq!(report.gates_before, 9);
+        assert_eq!(report.wires_before, 13);
+        // The duplicate AND makes its XOR constant 0, so the final AND is constant 0 as well
+        assert_eq!(optimized.gates.len(), 2);
+        assert_eq!(optimized.gates.len(), report.gates_after);
+        assert_eq!(optimized.num_wires, report.wires_after);
+        let all_inputs = (0..16)
+            .map(|i| vec![vec![i & 1 == 1, i & 2 == 2], vec![i & 4 == 4, i & 8 == 8]])
+            .collect();
+        assert_equivalent(&circuit, &optimized, all_inputs);
+    }
+
+    #[test]
+    fn test_not_folds_into_xor() {
+        let text = "3 5\n2 1 1\n1 1\n\n1 1 0 2 INV\n2 1 2 1 3 XOR\n1 1 3 4 INV\n";
+        let circuit: Circuit = text.parse().unwrap();
+        let (optimized, report) = circuit.optimize();
+        assert_eq!(report.gates_after, 1);
+        let all_inputs = (0..4)
+            .map(|i| vec![vec![i & 1 == 1], vec![i & 2 == 2]])
+            .collect();
+        assert_equivalent(&circuit, &optimized, all_inputs);
+    }
+
+    #[test]
+    fn test_extended_gates() {
+        // The NOT after the AND becomes a NAND and the XNOR of an inverted input becomes an XOR
+        let text = "5 9\n3 2 1 1\n1 2\n\n2 1 0 1 4 AND\n1 1 4 5 INV\n1 1 2 6 INV\n\
+                    2 1 6 3 7 XNOR\n3 1 0 5 1 8 MUX\n";
+        let circuit: Circuit = text.parse().unwrap();
+        let (optimized, report) = circuit.optimize();
+        assert_eq!(report.gates_after, 3);
+        let all_inputs = (0..16)
+            .map(|i| {
+                vec![
+                    vec![i & 1 == 1, i & 2 == 2],
+                    vec![i & 4 == 4],
+                    vec![i & 8 == 8],
+                ]
+            })
+            .collect();
+        assert_equivalent(&circuit, &optimized, all_inputs);
+    }
+
+    #[test]
+    fn test_add_circuit() {
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
+        let (mut optimized, report) = circuit.optimize(&TEST_SECRET).unwrap();
+        assert!(report.gates_after <= report.gates_before);
+        optimized.validate().unwrap();
+
+        for (a, b) in [(633u64, 300u64), (u64::MAX, 1), (0, 0)] {
+            assert_eq!(
+                optimized
+                    .evaluate_values::<u64>(&[&a, &b], Endianness::Little)
+                    .unwrap(),
+                circuit
+                    .evaluate_values::<u64>(&[&a, &b], Endianness::Little)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_library_circuits_shrink() {
+        for name in ["add64", "mul32", "sha256_preimage"] {
+            let circuit = standard_circuit(name).unwrap();
+            let (optimized, report) = circuit.optimize();
+            // Every library circuit has constants that fold away
+            assert!(
+                report.gates_after < report.gates_before,
+                "{}: {}",
+                name,
+                report
+            );
+            assert_equivalent(&circuit, &optimized, random_inputs(&circuit, 64));
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::{iter::zip, thread};
+
+use super::indexed::{Circuit, IndexedGate, WireId};
+
+/// Number of independent evaluations carried by one packed word
+pub const LANES: usize = 64;
+
+/// Layers smaller than this are evaluated on the calling thread, splitting them up costs more
+/// than it saves
+const MIN_GATES_PER_THREAD: usize = 256;
+
+/// Transposes up to 64 bit vectors of the same length into one word per bit, bit `j` of word `i`
+/// is bit `i` of vector `j`
+pub fn pack_bits(vectors: &[Vec<bool>]) -> Vec<u64> {
+    assert!(
+        vectors.len() <= LANES,
+        "at most {} vectors can be packed",
+        LANES
+    );
+    let length = vectors.first().map_or(0, |v| v.len());
+    let mut words = vec![0u64; length];
+    for (lane, vector) in vectors.iter().enumerate() {
+        assert_eq!(
+            vector.len(),
+            length,
+            "packed vectors must have the same length"
+        );
+        for (word, bit) in zip(words.iter_mut(), vector) {
+            *word |= (*bit as u64) << lane;
+        }
+    }
+    words
+}
+
+/// Inverse of `pack_bits`, returns the first `lanes` vectors held by the words
+pub fn unpack_bits(words: &[u64], lanes: usize) -> Vec<Vec<bool>> {
+    (0..lanes)
+        .map(|lane| words.iter().map(|word| (word >> lane) & 1 == 1).collect())
+        .collect()
+}
+
+impl IndexedGate {
+    pub fn evaluate_packed(&self, words: &[u64]) -> u64 {
+        let inputs = self
+            .input_wires
+            .iter()
+            .map(|wire| words[*wire])
+            .collect::<Vec<u64>>();
+        self.kind.apply_packed(&inputs)
+    }
+}
+
+impl Circuit {
+    /// Groups the gates by depth, a gate only reads wires written by earlier layers so the gates
+    /// of a layer can be evaluated in any order
+    pub fn layers(&self) -> Vec<Vec<usize>> {
+        let mut wire_depth = vec![0; self.num_wires];
+        let mut layers: Vec<Vec<usize>> = Vec::new();
+        for (i, gate) in self.gates.iter().enumerate() {
+            let depth = gate
+                .input_wires
+                .iter()
+                .map(|wire| wire_depth[*wire])
+                .max()
+                .unwrap_or(0);
+            for wire in gate.output_wires.iter() {
+                wire_depth[*wire] = depth + 1;
+            }
+            if layers.len() <= depth {
+                layers.resize(depth + 1, Vec::new());
+            }
+            layers[depth].push(i);
+        }
+        layers
+    }
+
+    /// Evaluates 64 input vectors at once, `input_words` holds one packed word per input bit.
+    /// Returns the packed value of every wire.
+    pub fn evaluate_packed(&self, input_words: &[u64]) -> Vec<u64> {
+        let mut words = vec![0u64; self.num_wires];
+        words[..input_words.len()].copy_from_slice(input_words);
+        for gate in self.gates.iter() {
+            words[gate.output_wires[0]] = gate.evaluate_packed(&words);
+        }
+        words
+    }
+
+    /// Same as `evaluate_packed`, but the gates of each layer are spread over `threads` threads
+    pub fn evaluate_packed_parallel(&self, input_words: &[u64], threads: usize) -> Vec<u64> {
+        let mut words = vec![0u64; self.num_wires];
+        words[..input_words.len()].copy_from_slice(input_words);
+        for layer in self.layers() {
+            if threads <= 1 || layer.len() < 2 * MIN_GATES_PER_THREAD {
+                for gate in layer.iter().map(|i| &self.gates[*i]) {
+                    words[gate.output_wires[0]] = gate.evaluate_packed(&words);
+                }
+                continue;
+            }
+
+            let chunk_size = layer.len().div_ceil(threads).max(MIN_GATES_PER_THREAD);
+            let outputs = thread::scope(|s| {
+                let words = &words;
+                let handles = layer
+                    .chunks(chunk_size)
+                    .map(|chunk| {
+                        s.spawn(move || {
+                            chunk
+                                .iter()
+                                .map(|i| {
+                                    let gate = &self.gates[*i];
+                                    (gate.output_wires[0], gate.evaluate_packed(words))
+                                })
+                                .collect::<Vec<(WireId, u64)>>()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .flat_map(|handle| handle.join().expect("evaluation thread panicked"))
+                    .collect::<Vec<(WireId, u64)>>()
+            });
+            for (wire, word) in outputs {
+                words[wire] = word;
+            }
+        }
+        words
+    }
+
+    /// Evaluates any number of input vectors, 64 at a time. Each entry of `inputs` is laid out
+    /// like the argument of `evaluate`, pass `threads > 1` to also evaluate layers in parallel.
+    pub fn evaluate_batch(&self, inputs: &[Vec<Vec<bool>>], threads: usize) -> Vec<Vec<Vec<bool>>> {
+        let output_start = self.output_start();
+        let mut outputs = Vec::with_capacity(inputs.len());
+        for chunk in inputs.chunks(LANES) {
+            let combined_inputs = chunk
+                .iter()
+                .map(|input| self.combine_inputs(input))
+                .collect::<Vec<Vec<bool>>>();
+
+            let words = self.evaluate_packed_parallel(&pack_bits(&combined_inputs), threads);
+            for bits in unpack_bits(&words[output_start..], chunk.len()) {
+                outputs.push(self.split_outputs(&bits));
+            }
+        }
+        outputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::key::rand::{self, Rng};
+
+    use crate::{
+        circuit::indexed::Circuit,
+        utils::conversions::{bool_array_to_number, number_to_bool_array},
+    };
+
+    use super::{pack_bits, unpack_bits};
+
+    #[test]
+    fn test_pack_round_trip() {
+        let mut rng = rand::thread_rng();
+        let vectors = (0..37)
+            .map(|_| (0..10).map(|_| rng.gen()).collect())
+            .collect::<Vec<Vec<bool>>>();
+        assert_eq!(unpack_bits(&pack_bits(&vectors), vectors.len()), vectors);
+    }
+
+    #[test]
+    fn test_batch_matches_evaluate() {
+        let circuit = Circuit::from_bristol("circuits/add.txt").unwrap();
+        let mut rng = rand::thread_rng();
+        let inputs = (0..150)
+            .map(|_| {
+                vec![
+                    number_to_bool_array(rng.gen::<u32>() as usize, 64),
+                    number_to_bool_array(rng.gen::<u32>() as usize, 64),
+                ]
+            })
+            .collect::<Vec<Vec<Vec<bool>>>>();
+
+        let outputs = circuit.evaluate_batch(&inputs, 1);
+        assert_eq!(outputs.len(), inputs.len());
+        for (input, output) in inputs.iter().zip(outputs) {
+            assert_eq!(output, circuit.evaluate(input.clone()));
+            assert_eq!(
+                bool_array_to_number(output[0].clone()),
+                bool_array_to_number(input[0].clone()) + bool_array_to_number(input[1].clone())
+            );
+        }
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        // A wide layer of XOR gates so the parallel path actually splits the work
+        let width = 2048;
+        let mut text = format!(
+            "{} {}\n2 {} {}\n1 {}\n\n",
+            width,
+            3 * width,
+            width,
+            width,
+            width
+        );
+        for i in 0..width {
+            text += &format!("2 1 {} {} {} XOR\n", i, width + i, 2 * width + i);
+        }
+        let circuit: Circuit = text.parse().unwrap();
+        assert_eq!(circuit.layers().len(), 1);
+
+        let mut rng = rand::thread_rng();
+        let input_words = (0..2 * width).map(|_| rng.gen()).collect::<Vec<u64>>();
+        assert_eq!(
+            circuit.evaluate_packed_parallel(&input_words, 4),
+            circuit.evaluate_packed(&input_words)
+        );
+    }
+
+    #[test]
+    fn test_layers_respect_dependencies() {
+        let circuit = Circuit::from_bristol("circuits/add.txt").unwrap();
+        let layers = circuit.layers();
+        assert_eq!(
+            layers.iter().map(|l| l.len()).sum::<usize>(),
+            circuit.gates.len()
+        );
+
+        let mut written = vec![false; circuit.num_wires];
+        let inputs = circuit.input_wire_sizes.iter().sum::<usize>();
+        written[..inputs].iter_mut().for_each(|w| *w = true);
+        for layer in layers {
+            for i in layer.iter() {
+                assert!(circuit.gates[*i].input_wires.iter().all(|w| written[*w]));
+            }
+            for i in layer.iter() {
+                for w in circuit.gates[*i].output_wires.iter() {
+                    written[*w] = true;
+                }
+            }
+        }
+    }
+}
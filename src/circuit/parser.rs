@@ -0,0 +1,398 @@
+use std::{io, iter::Peekable};
+
+use super::{
+    error::CircuitParseError,
+    gate::{GateKind, GateType},
+    indexed::{IndexedGate, WireId},
+    BristolFormat,
+};
+
+pub type NumberedLine = io::Result<(usize, String)>;
+
+pub struct Header {
+    pub num_gates: usize,
+    pub num_wires: usize,
+    pub input_wire_sizes: Vec<usize>,
+    pub output_wire_sizes: Vec<usize>,
+    pub format: BristolFormat,
+}
+
+/// Whitespace separated tokens of a single circuit line, tracking the column of each token so
+/// errors can point at it.
+pub struct LineTokens<'a> {
+    line: usize,
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> LineTokens<'a> {
+    pub fn new(line: usize, text: &'a str) -> Self {
+        LineTokens {
+            line,
+            text,
+            offset: 0,
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Column just past the last consumed token, used when a token is missing
+    pub fn column(&self) -> usize {
+        self.offset + 1
+    }
+
+    /// Returns the next token with its 1-based column
+    pub fn next_token(&mut self) -> Option<(usize, &'a str)> {
+        let rest = &self.text[self.offset..];
+        let start = self.offset + rest.find(|c: char| !c.is_whitespace())?;
+        let len = self.text[start..]
+            .find(char::is_whitespace)
+            .unwrap_or(self.text.len() - start);
+        self.offset = start + len;
+        Some((start + 1, &self.text[start..start + len]))
+    }
+
+    pub fn expect_token(
+        &mut self,
+        expected: &'static str,
+    ) -> Result<(usize, &'a str), CircuitParseError> {
+        let column = self.column();
+        self.next_token()
+            .ok_or(CircuitParseError::UnexpectedEndOfLine {
+                line: self.line,
+                column,
+                expected,
+            })
+    }
+
+    /// Parses the next token as a number, returning it with its column
+    pub fn expect_number(
+        &mut self,
+        expected: &'static str,
+    ) -> Result<(usize, usize), CircuitParseError> {
+        let (column, token) = self.expect_token(expected)?;
+        token
+            .parse()
+            .map(|n| (column, n))
+            .map_err(|_| CircuitParseError::InvalidNumber {
+                line: self.line,
+                column,
+                token: token.to_string(),
+            })
+    }
+
+    /// Returns the last token left on the line with its column, without consuming anything
+    pub fn last_token(&self) -> Option<(usize, &'a str)> {
+        let rest = self.text[self.offset..].trim_end();
+        let token = rest.split_whitespace().next_back()?;
+        let start = self.offset + rest.len() - token.len();
+        Some((start + 1, token))
+    }
+
+    /// Errors if anything other than whitespace is left on the line
+    pub fn expect_end(&mut self) -> Result<(), CircuitParseError> {
+        match self.next_token() {
+            Some((column, token)) => Err(CircuitParseError::UnexpectedToken {
+                line: self.line,
+                column,
+                token: token.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+fn next_header_line(
+    lines: &mut impl Iterator<Item = NumberedLine>,
+    line: usize,
+) -> Result<(usize, String), CircuitParseError> {
+    lines
+        .next()
+        .unwrap_or(Err(io::ErrorKind::UnexpectedEof.into()))
+        .map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                CircuitParseError::InvalidHeader {
+                    line,
+                    column: 1,
+                    reason: "header must contain the gate/wire counts, inputs and outputs"
+                        .to_string(),
+                }
+            } else {
+                e.into()
+            }
+        })
+}
+
+/// A Bristol Fashion wire size line is `<count> <size_1> ... <size_count>`
+fn is_fashion_wire_sizes(text: &str) -> bool {
+    let numbers = text
+        .split_whitespace()
+        .map(|token| token.parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>();
+    match numbers {
+        Ok(numbers) => !numbers.is_empty() && numbers.len() == numbers[0] + 1,
+        Err(_) => false,
+    }
+}
+
+/// Reads the circuit header, detecting which dialect it is written in. Legacy Bristol has a
+/// single `<input 1> <input 2> <output>` line, while Bristol Fashion has separate input and
+/// output lines that each start with the number of values that follow.
+pub fn parse_header<I: Iterator<Item = NumberedLine>>(
+    lines: &mut Peekable<I>,
+) -> Result<Header, CircuitParseError> {
+    // First line of header contains the number of gates and wires
+    let (line, text) = next_header_line(lines, 1)?;
+    let mut tokens = LineTokens::new(line, &text);
+    let num_gates = tokens.expect_number("number of gates")?.1;
+    let num_wires = tokens.expect_number("number of wires")?.1;
+    tokens.expect_end()?;
+
+    // Second line of header contains the input wires, and for legacy circuits the output
+    let (line, text) = next_header_line(lines, 2)?;
+    let input_line = line;
+    let legacy_sizes = text
+        .split_whitespace()
+        .map(|token| token.parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .ok()
+        .filter(|sizes| sizes.len() == 3);
+    let fashion_outputs_follow =
+        matches!(lines.peek(), Some(Ok((_, next))) if is_fashion_wire_sizes(next));
+
+    let (input_wire_sizes, output_wire_sizes, output_line, format) = match legacy_sizes {
+        Some(sizes) if !fashion_outputs_follow => (
+            sizes[0..2].to_vec(),
+            vec![sizes[2]],
+            line,
+            BristolFormat::Legacy,
+        ),
+        _ => {
+            let input_wire_sizes = parse_wire_sizes(&mut LineTokens::new(line, &text), "input")?;
+
+            // Third line of header contains the output wires
+            let (line, text) = next_header_line(lines, 3)?;
+            let output_wire_sizes = parse_wire_sizes(&mut LineTokens::new(line, &text), "output")?;
+            (
+                input_wire_sizes,
+                output_wire_sizes,
+                line,
+                BristolFormat::Fashion,
+            )
+        }
+    };
+
+    // Inputs are the first wires and outputs the last, so together they can't need more wires
+    // than the header declares
+    let overflow = |line| CircuitParseError::InvalidHeader {
+        line,
+        column: 1,
+        reason: "wire sizes overflow".to_string(),
+    };
+    let inputs = sum_wire_sizes(&input_wire_sizes).ok_or_else(|| overflow(input_line))?;
+    let required = sum_wire_sizes(&output_wire_sizes)
+        .and_then(|outputs| inputs.checked_add(outputs))
+        .ok_or_else(|| overflow(output_line))?;
+    if required > num_wires {
+        return Err(CircuitParseError::WireCountMismatch {
+            line: 1,
+            column: 1,
+            expected: num_wires,
+            found: required,
+        });
+    }
+
+    Ok(Header {
+        num_gates,
+        num_wires,
+        input_wire_sizes,
+        output_wire_sizes,
+        format,
+    })
+}
+
+/// Total number of wires in the groups, or `None` if it doesn't fit in a `usize`
+fn sum_wire_sizes(sizes: &[usize]) -> Option<usize> {
+    sizes
+        .iter()
+        .try_fold(0usize, |total, &size| total.checked_add(size))
+}
+
+/// Parses a header line of the form `<count> <size_1> ... <size_count>`
+fn parse_wire_sizes(tokens: &mut LineTokens, kind: &str) -> Result<Vec<usize>, CircuitParseError> {
+    let (column, count) = tokens.expect_number("number of wire groups")?;
+    let sizes = (0..count)
+        .map(|_| {
+            tokens
+                .expect_number("wire group size")
+                .map(|(_, size)| size)
+        })
+        .collect::<Result<Vec<usize>, CircuitParseError>>()
+        .map_err(|_| CircuitParseError::InvalidHeader {
+            line: tokens.line(),
+            column,
+            reason: format!("expected {} {} wire sizes", count, kind),
+        })?;
+    tokens.expect_end()?;
+    Ok(sizes)
+}
+
+/// The inputs and outputs a gate line of type `name` declaring `num_outputs` outputs must have
+fn expected_arity(name: &str, num_outputs: usize) -> Option<(usize, usize)> {
+    match name {
+        "EQ" => Some((1, 1)),
+        "CONST0" | "CONST1" => Some((0, 1)),
+        "MAND" => Some((num_outputs.saturating_mul(2), num_outputs)),
+        _ => GateType::try_from(name)
+            .ok()
+            .map(|gate_type| gate_type.arity()),
+    }
+}
+
+/// Parses a gate line of the form `<inputs> <outputs> <wire>... <gate type>`. A Bristol Fashion
+/// `MAND` line is expanded into one AND gate per output, so a single line can produce several
+/// gates. `CONST0` and `CONST1` lines from other toolchains load as the same constant gates as
+/// `EQ`.
+pub fn parse_gate(
+    tokens: &mut LineTokens,
+    num_wires: usize,
+) -> Result<Vec<IndexedGate>, CircuitParseError> {
+    let num_inputs = tokens.expect_number("number of gate inputs")?.1;
+    let num_outputs = tokens.expect_number("number of gate outputs")?.1;
+    let num_operands = match num_inputs.checked_add(num_outputs) {
+        Some(num_operands) => num_operands,
+        None => {
+            // No gate takes that many wires, so report it against the gate type that ends the line
+            let (column, name) =
+                tokens
+                    .last_token()
+                    .ok_or(CircuitParseError::UnexpectedEndOfLine {
+                        line: tokens.line(),
+                        column: tokens.column(),
+                        expected: "gate type",
+                    })?;
+            let expected = expected_arity(name, num_outputs).ok_or_else(|| {
+                CircuitParseError::UnknownGate {
+                    line: tokens.line(),
+                    column,
+                    name: name.to_string(),
+                }
+            })?;
+            return Err(CircuitParseError::GateArityMismatch {
+                line: tokens.line(),
+                column,
+                gate: name.to_string(),
+                expected,
+                found: (num_inputs, num_outputs),
+            });
+        }
+    };
+    let operands = (0..num_operands)
+        .map(|_| tokens.expect_token("wire index"))
+        .collect::<Result<Vec<(usize, &str)>, CircuitParseError>>()?;
+    let (column, name) = tokens.expect_token("gate type")?;
+    tokens.expect_end()?;
+
+    let arity_mismatch = |expected: (usize, usize)| CircuitParseError::GateArityMismatch {
+        line: tokens.line(),
+        column,
+        gate: name.to_string(),
+        expected,
+        found: (num_inputs, num_outputs),
+    };
+    let to_wire = |(column, token): &(usize, &str)| {
+        let index = token
+            .parse::<usize>()
+            .map_err(|_| CircuitParseError::InvalidNumber {
+                line: tokens.line(),
+                column: *column,
+                token: token.to_string(),
+            })?;
+        if index >= num_wires {
+            return Err(CircuitParseError::WireIndexOutOfRange {
+                line: tokens.line(),
+                column: *column,
+                index,
+                num_wires,
+            });
+        }
+        Ok(index)
+    };
+
+    match name {
+        "EQ" => {
+            // The input of an EQ gate is the constant being assigned rather than a wire
+            if (num_inputs, num_outputs) != (1, 1) {
+                return Err(arity_mismatch((1, 1)));
+            }
+            let (column, token) = operands[0];
+            let value = match token {
+                "0" => false,
+                "1" => true,
+                _ => {
+                    return Err(CircuitParseError::InvalidNumber {
+                        line: tokens.line(),
+                        column,
+                        token: token.to_string(),
+                    })
+                }
+            };
+            Ok(vec![IndexedGate {
+                kind: GateKind::Constant(value),
+                input_wires: vec![],
+                output_wires: vec![to_wire(&operands[1])?],
+            }])
+        }
+        "CONST0" | "CONST1" => {
+            if (num_inputs, num_outputs) != (0, 1) {
+                return Err(arity_mismatch((0, 1)));
+            }
+            Ok(vec![IndexedGate {
+                kind: GateKind::Constant(name == "CONST1"),
+                input_wires: vec![],
+                output_wires: vec![to_wire(&operands[0])?],
+            }])
+        }
+        "MAND" => {
+            if num_inputs != 2 * num_outputs {
+                return Err(arity_mismatch((2 * num_outputs, num_outputs)));
+            }
+            let wires = operands
+                .iter()
+                .map(to_wire)
+                .collect::<Result<Vec<WireId>, CircuitParseError>>()?;
+            let (a, rest) = wires.split_at(num_outputs);
+            let (b, c) = rest.split_at(num_outputs);
+            Ok((0..num_outputs)
+                .map(|i| IndexedGate {
+                    kind: GateKind::Op(GateType::AND),
+                    input_wires: vec![a[i], b[i]],
+                    output_wires: vec![c[i]],
+                })
+                .collect())
+        }
+        _ => {
+            let gate_type =
+                GateType::try_from(name).map_err(|e| CircuitParseError::UnknownGate {
+                    line: tokens.line(),
+                    column,
+                    name: e.0,
+                })?;
+            if gate_type.arity() != (num_inputs, num_outputs) {
+                return Err(arity_mismatch(gate_type.arity()));
+            }
+            let mut input_wires = operands
+                .iter()
+                .map(to_wire)
+                .collect::<Result<Vec<WireId>, CircuitParseError>>()?;
+            let output_wires = input_wires.split_off(num_inputs);
+            Ok(vec![IndexedGate {
+                kind: GateKind::Op(gate_type),
+                input_wires,
+                output_wires,
+            }])
+        }
+    }
+}
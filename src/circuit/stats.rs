@@ -0,0 +1,228 @@
+use std::{collections::BTreeMap, fmt};
+
+use bitcoin::{taproot::TAPROOT_CONTROL_BASE_SIZE, Witness};
+
+use crate::transactions::{
+    generate_anti_contradiction_script, generate_gate_response_script, taproot_tree_depth,
+    unspendable_internal_key,
+};
+
+use super::{wire::HashTuple, BristolCircuit};
+
+/// Size of a schnorr signature with the default sighash type
+const SIGNATURE_SIZE: usize = 64;
+const PREIMAGE_SIZE: usize = 32;
+const TAPROOT_CONTROL_NODE_SIZE: usize = 32;
+
+/// Estimated on-chain cost of disputing a circuit, in bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// Total size of the response leaf scripts, one per gate
+    pub response_scripts_size: usize,
+    pub challenge_tree_depth: u8,
+    pub response_tree_depth: u8,
+    pub equivocation_tree_depth: u8,
+    /// Witness of the largest gate response spend
+    pub worst_response_witness_size: usize,
+    /// Witness of an equivocation spend, the same for every wire
+    pub equivocation_witness_size: usize,
+}
+
+/// Shape and cost of a circuit, used to decide whether a program is affordable before any funds
+/// are committed to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitStats {
+    pub num_gates: usize,
+    pub num_wires: usize,
+    /// Gate counts keyed by their name in a Bristol file
+    pub gates_by_type: BTreeMap<&'static str, usize>,
+    /// Number of gates on the longest path from an input to an output
+    pub depth: usize,
+    /// Most gates reading the same wire
+    pub max_fan_out: usize,
+    pub average_fan_out: f64,
+    pub cost: CostEstimate,
+}
+
+/// Depth of a tree with one leaf per script, a single leaf sits at the root
+fn tree_depth(num_leaves: usize) -> u8 {
+    if num_leaves <= 1 {
+        0
+    } else {
+        taproot_tree_depth(num_leaves)
+    }
+}
+
+fn witness_size(stack: Vec<Vec<u8>>, script_size: usize, tree_depth: u8) -> usize {
+    let mut witness = Witness::from_slice(&stack);
+    witness.push(vec![0; script_size]);
+    witness.push(vec![
+        0;
+        TAPROOT_CONTROL_BASE_SIZE
+            + TAPROOT_CONTROL_NODE_SIZE * tree_depth as usize
+    ]);
+    witness.size()
+}
+
+impl BristolCircuit {
+    pub fn stats(&self) -> CircuitStats {
+        let mut gates_by_type = BTreeMap::new();
+        let mut fan_out = vec![0; self.wires.len()];
+        for gate in self.gates.iter() {
+            *gates_by_type.entry(gate.get_name()).or_insert(0) += 1;
+            for wire in gate.get_input_wires() {
+                fan_out[wire.lock().unwrap().index.expect("wire index is not set")] += 1;
+            }
+        }
+
+        // Keys and hashes are fixed size pushes, so placeholders give the real script sizes
+        let placeholder_key = unspendable_internal_key();
+        let challenge_tree_depth = tree_depth(self.gates.len());
+        let response_tree_depth = challenge_tree_depth;
+        let equivocation_tree_depth = tree_depth(self.wires.len() + 2);
+
+        let mut response_scripts_size = 0;
+        let mut worst_response_witness_size = 0;
+        for gate in self.gates.iter() {
+            let script_size = generate_gate_response_script(gate, &[0; 32], placeholder_key).len();
+            response_scripts_size += script_size;
+
+            // Signature, a preimage per wire and the challenge preimage
+            let mut stack = vec![vec![0; SIGNATURE_SIZE]];
+            let num_preimages = gate.get_input_wires().len() + gate.get_output_wires().len() + 1;
+            stack.extend(vec![vec![0; PREIMAGE_SIZE]; num_preimages]);
+            worst_response_witness_size = worst_response_witness_size.max(witness_size(
+                stack,
+                script_size,
+                response_tree_depth,
+            ));
+        }
+
+        let equivocation_script_size = generate_anti_contradiction_script(
+            HashTuple {
+                zero: [0; 32],
+                one: [0; 32],
+            },
+            placeholder_key,
+        )
+        .len();
+        let equivocation_witness_size = witness_size(
+            vec![
+                vec![0; SIGNATURE_SIZE],
+                vec![0; PREIMAGE_SIZE],
+                vec![0; PREIMAGE_SIZE],
+            ],
+            equivocation_script_size,
+            equivocation_tree_depth,
+        );
+
+        CircuitStats {
+            num_gates: self.gates.len(),
+            num_wires: self.wires.len(),
+            gates_by_type,
+            depth: self.to_circuit().layers().len(),
+            max_fan_out: fan_out.iter().copied().max().unwrap_or(0),
+            average_fan_out: fan_out.iter().sum::<usize>() as f64 / fan_out.len().max(1) as f64,
+            cost: CostEstimate {
+                response_scripts_size,
+                challenge_tree_depth,
+                response_tree_depth,
+                equivocation_tree_depth,
+                worst_response_witness_size,
+                equivocation_witness_size,
+            },
+        }
+    }
+}
+
+impl fmt::Display for CircuitStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "gates: {}, wires: {}", self.num_gates, self.num_wires)?;
+        let gates_by_type = self
+            .gates_by_type
+            .iter()
+            .map(|(name, count)| format!("{}: {}", name, count))
+            .collect::<Vec<String>>();
+        writeln!(f, "gate types: {}", gates_by_type.join(", "))?;
+        writeln!(
+            f,
+            "depth: {}, fan-out: max {}, average {:.2}",
+            self.depth, self.max_fan_out, self.average_fan_out
+        )?;
+        writeln!(
+            f,
+            "response scripts: {} bytes",
+            self.cost.response_scripts_size
+        )?;
+        writeln!(
+            f,
+            "taproot depth: challenge {}, response {}, equivocation {}",
+            self.cost.challenge_tree_depth,
+            self.cost.response_tree_depth,
+            self.cost.equivocation_tree_depth
+        )?;
+        write!(
+            f,
+            "worst case witness: response {} bytes, equivocation {} bytes",
+            self.cost.worst_response_witness_size, self.cost.equivocation_witness_size
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        circuit::{tests::TEST_SECRET, BristolCircuit},
+        transactions::{generate_gate_response_script, unspendable_internal_key},
+    };
+
+    #[test]
+    fn test_add_circuit_stats() {
+        let circuit = BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
+        let stats = circuit.stats();
+
+        assert_eq!(stats.num_gates, 376);
+        assert_eq!(stats.num_wires, 504);
+        assert_eq!(stats.gates_by_type.values().sum::<usize>(), 376);
+        assert_eq!(stats.depth, circuit.to_circuit().layers().len());
+        assert!(stats.max_fan_out >= 2);
+
+        // 376 gates and 506 equivocation leaves both need 9 levels
+        assert_eq!(stats.cost.challenge_tree_depth, 9);
+        assert_eq!(stats.cost.response_tree_depth, 9);
+        assert_eq!(stats.cost.equivocation_tree_depth, 9);
+
+        let total = circuit
+            .gates
+            .iter()
+            .map(|gate| {
+                generate_gate_response_script(gate, &[1; 32], unspendable_internal_key()).len()
+            })
+            .sum::<usize>();
+        assert_eq!(stats.cost.response_scripts_size, total);
+    }
+
+    #[test]
+    fn test_witness_sizes() {
+        // A single two input gate, so its response is the worst case
+        let text = "1 3\n2 1 1\n1 1\n\n2 1 0 1 2 AND\n";
+        let circuit = BristolCircuit::from_str(text, &TEST_SECRET).unwrap();
+        let stats = circuit.stats();
+        let script_size =
+            generate_gate_response_script(&circuit.gates[0], &[0; 32], unspendable_internal_key())
+                .len();
+
+        // Item count, signature, four preimages, script and a control block with no path
+        assert_eq!(stats.cost.response_tree_depth, 0);
+        assert_eq!(
+            stats.cost.worst_response_witness_size,
+            1 + 65 + 4 * 33 + 3 + script_size + 34
+        );
+        // Five equivocation leaves sit three levels deep
+        assert_eq!(stats.cost.equivocation_tree_depth, 3);
+        assert_eq!(
+            stats.cost.equivocation_witness_size,
+            1 + 65 + 2 * 33 + 1 + 104 + 1 + 33 + 3 * 32
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+
+use super::{indexed::Circuit, BristolCircuit};
+
+/// The bits a single gate read and wrote during an evaluation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GateTrace {
+    pub input_bits: Vec<bool>,
+    pub output_bits: Vec<bool>,
+}
+
+/// Full assignment of a circuit evaluation. The prover persists this as the exact assignment it
+/// committed to, and the verifier can replay it gate by gate to find where it goes wrong.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionTrace {
+    pub wire_values: Vec<bool>,
+    pub gates: Vec<GateTrace>,
+}
+
+impl ExecutionTrace {
+    /// Builds the trace of `circuit` from the value of every wire
+    pub fn from_wire_values(circuit: &Circuit, wire_values: Vec<bool>) -> Self {
+        let gates = circuit
+            .gates
+            .iter()
+            .map(|gate| GateTrace {
+                input_bits: gate.input_wires.iter().map(|w| wire_values[*w]).collect(),
+                output_bits: gate.output_wires.iter().map(|w| wire_values[*w]).collect(),
+            })
+            .collect();
+        ExecutionTrace { wire_values, gates }
+    }
+
+    /// Replays the trace against `circuit`, returning the index of the first gate that either
+    /// computes the wrong output for its inputs or disagrees with the recorded wire values. A
+    /// trace that is missing gates is faulty at the first missing one.
+    pub fn first_faulty_gate(&self, circuit: &Circuit) -> Option<usize> {
+        let wire_value = |wire: &usize| self.wire_values.get(*wire).copied();
+        for (i, gate) in circuit.gates.iter().enumerate() {
+            let Some(trace) = self.gates.get(i) else {
+                return Some(i);
+            };
+            let input_bits = gate.input_wires.iter().map(wire_value).collect::<Vec<_>>();
+            let output_bits = gate.output_wires.iter().map(wire_value).collect::<Vec<_>>();
+            let consistent = trace.input_bits.len() == gate.input_wires.len()
+                && input_bits
+                    == trace
+                        .input_bits
+                        .iter()
+                        .map(|b| Some(*b))
+                        .collect::<Vec<_>>()
+                && output_bits
+                    == trace
+                        .output_bits
+                        .iter()
+                        .map(|b| Some(*b))
+                        .collect::<Vec<_>>()
+                && trace.output_bits == vec![gate.kind.apply(&trace.input_bits)];
+            if !consistent {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    /// Values of the circuit's output wires, split into the declared outputs
+    pub fn outputs(&self, circuit: &Circuit) -> Vec<Vec<bool>> {
+        circuit.split_outputs(&self.wire_values[circuit.output_start()..])
+    }
+}
+
+impl Circuit {
+    pub fn evaluate_with_trace(&self, inputs: Vec<Vec<bool>>) -> ExecutionTrace {
+        let wire_values = self.evaluate_wires(&self.combine_inputs(&inputs));
+        ExecutionTrace::from_wire_values(self, wire_values)
+    }
+}
+
+impl BristolCircuit {
+    /// Evaluates the circuit and records the value of every wire and the bits of every gate.
+    /// Wires that no gate writes are recorded as 0.
+    pub fn evaluate_with_trace(&mut self, inputs: Vec<Vec<bool>>) -> ExecutionTrace {
+        self.evaluate(inputs);
+        let wire_values = self
+            .wires
+            .iter()
+            .map(|wire_arcm| wire_arcm.lock().unwrap().selector.unwrap_or(false))
+            .collect();
+        let gates = self
+            .gates
+            .iter_mut()
+            .map(|gate| GateTrace {
+                input_bits: gate.get_input_bits(),
+                output_bits: gate.get_output_bits(),
+            })
+            .collect();
+        ExecutionTrace { wire_values, gates }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        circuit::{indexed::Circuit, tests::TEST_SECRET, BristolCircuit},
+        utils::conversions::{bool_array_to_number, number_to_bool_array},
+    };
+
+    use super::ExecutionTrace;
+
+    fn add_inputs() -> Vec<Vec<bool>> {
+        vec![number_to_bool_array(633, 64), number_to_bool_array(300, 64)]
+    }
+
+    #[test]
+    fn test_trace_matches_indexed_circuit() {
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
+        let indexed = circuit.to_circuit();
+
+        let trace = circuit.evaluate_with_trace(add_inputs());
+        assert_eq!(trace, indexed.evaluate_with_trace(add_inputs()));
+        assert_eq!(trace.gates.len(), circuit.gates.len());
+        assert_eq!(
+            bool_array_to_number(trace.outputs(&indexed)[0].clone()),
+            933
+        );
+        assert_eq!(trace.first_faulty_gate(&indexed), None);
+    }
+
+    #[test]
+    fn test_trace_serialization() {
+        let circuit = Circuit::from_bristol("circuits/add.txt").unwrap();
+        let trace = circuit.evaluate_with_trace(add_inputs());
+
+        let json = serde_json::to_string(&trace).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ExecutionTrace>(&json).unwrap(),
+            trace
+        );
+    }
+
+    #[test]
+    fn test_replay_finds_first_faulty_gate() {
+        let circuit = Circuit::from_bristol("circuits/add.txt").unwrap();
+        let honest = circuit.evaluate_with_trace(add_inputs());
+
+        // Flip the output of a gate, later gates are consistent with the flipped wire
+        let mut wire_values = honest.wire_values.clone();
+        let output = circuit.gates[100].output_wires[0];
+        wire_values[output] = !wire_values[output];
+        let mut faulty = ExecutionTrace::from_wire_values(&circuit, wire_values);
+        assert_eq!(faulty.first_faulty_gate(&circuit), Some(100));
+
+        // A gate claiming different inputs than the recorded wire values is also caught
+        faulty = honest.clone();
+        faulty.gates[5].input_bits[0] = !faulty.gates[5].input_bits[0];
+        assert_eq!(faulty.first_faulty_gate(&circuit), Some(5));
+
+        faulty = honest;
+        faulty.gates.truncate(10);
+        assert_eq!(faulty.first_faulty_gate(&circuit), Some(10));
+    }
+}
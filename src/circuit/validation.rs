@@ -0,0 +1,196 @@
+use super::{error::CircuitValidationError, gate::SafeWire, BristolCircuit};
+
+/// Total number of wires in the groups, or `None` if it doesn't fit in a `usize`
+fn checked_sum(sizes: &[usize]) -> Option<usize> {
+    sizes
+        .iter()
+        .try_fold(0usize, |total, &size| total.checked_add(size))
+}
+
+/// Index of a wire used by `gate`. Gates of a hand built or mutated circuit can point past the
+/// circuit's wires, which is reported instead of indexed.
+fn gate_wire(
+    errors: &mut Vec<CircuitValidationError>,
+    gate: usize,
+    wire: &SafeWire,
+    num_wires: usize,
+) -> Option<usize> {
+    let index = wire.lock().unwrap().index;
+    let in_range = index.filter(|&index| index < num_wires);
+    if in_range.is_none() {
+        errors.push(CircuitValidationError::HeaderMismatch {
+            reason: format!(
+                "gate {} uses wire {:?} outside the {} wires",
+                gate, index, num_wires
+            ),
+        });
+    }
+    in_range
+}
+
+impl BristolCircuit {
+    /// Checks the circuit is well formed before it's used to build any transactions. Inputs
+    /// must be the first wires and outputs the last, every wire must be written exactly once
+    /// before it's read, and every wire must either be read or be an output. All problems found
+    /// are returned rather than just the first.
+    pub fn validate(&self) -> Result<(), Vec<CircuitValidationError>> {
+        let mut errors = Vec::new();
+        let num_wires = self.wires.len();
+        let num_inputs = checked_sum(&self.input_wire_sizes);
+        let num_outputs = checked_sum(&self.output_wire_sizes);
+
+        match num_inputs.zip(num_outputs) {
+            Some((num_inputs, num_outputs)) => {
+                if num_inputs
+                    .checked_add(num_outputs)
+                    .is_none_or(|required| required > num_wires)
+                {
+                    errors.push(CircuitValidationError::HeaderMismatch {
+                        reason: format!(
+                            "{} input and {} output wires don't fit in {} wires",
+                            num_inputs, num_outputs, num_wires
+                        ),
+                    });
+                }
+            }
+            None => errors.push(CircuitValidationError::HeaderMismatch {
+                reason: "input and output wire sizes overflow".to_string(),
+            }),
+        }
+        let num_inputs = num_inputs.unwrap_or(usize::MAX);
+        let num_outputs = num_outputs.unwrap_or(usize::MAX);
+        for (i, wire) in self.wires.iter().enumerate() {
+            let index = wire.lock().unwrap().index;
+            if index != Some(i) {
+                errors.push(CircuitValidationError::HeaderMismatch {
+                    reason: format!("wire at position {} has index {:?}", i, index),
+                });
+            }
+        }
+
+        let output_start = num_wires.saturating_sub(num_outputs);
+        let mut driven = vec![false; num_wires];
+        let mut read = vec![false; num_wires];
+        driven[..num_inputs.min(num_wires)].fill(true);
+
+        for (gate_index, gate) in self.gates.iter().enumerate() {
+            for wire in gate.get_input_wires() {
+                let Some(wire) = gate_wire(&mut errors, gate_index, wire, num_wires) else {
+                    continue;
+                };
+                if !driven[wire] {
+                    errors.push(CircuitValidationError::WireReadBeforeWritten {
+                        gate: gate_index,
+                        wire,
+                    });
+                }
+                read[wire] = true;
+            }
+            for wire in gate.get_output_wires() {
+                let Some(wire) = gate_wire(&mut errors, gate_index, wire, num_wires) else {
+                    continue;
+                };
+                if driven[wire] {
+                    errors.push(CircuitValidationError::MultiplyDrivenWire {
+                        wire,
+                        gate: gate_index,
+                    });
+                }
+                driven[wire] = true;
+            }
+        }
+
+        for wire in 0..num_wires {
+            let is_output = wire >= output_start;
+            if is_output && !driven[wire] {
+                errors.push(CircuitValidationError::UndrivenOutput { wire });
+            }
+            if !is_output && !read[wire] {
+                errors.push(CircuitValidationError::UnusedWire { wire });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::{error::CircuitValidationError, tests::TEST_SECRET, BristolCircuit};
+
+    fn validate(text: &str) -> Result<(), Vec<CircuitValidationError>> {
+        BristolCircuit::from_str(text, &TEST_SECRET)
+            .unwrap()
+            .validate()
+    }
+
+    #[test]
+    fn test_valid_circuits() {
+        let circuit = BristolCircuit::from_bristol("circuits/add.txt", &TEST_SECRET).unwrap();
+        assert_eq!(circuit.validate(), Ok(()));
+
+        assert_eq!(
+            validate("2 5\n2 1 2\n1 1\n\n2 1 0 1 3 AND\n2 1 2 3 4 XOR\n"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_wire_read_before_written() {
+        let errors = validate("2 4\n2 1 1\n1 1\n\n2 1 0 2 3 XOR\n2 1 0 1 2 AND\n").unwrap_err();
+        assert_eq!(
+            errors,
+            vec![CircuitValidationError::WireReadBeforeWritten { gate: 0, wire: 2 }]
+        );
+    }
+
+    #[test]
+    fn test_multiply_driven_wires() {
+        let errors = validate("2 4\n2 1 1\n1 1\n\n2 1 0 1 3 AND\n2 1 0 1 3 XOR\n").unwrap_err();
+        assert!(errors.contains(&CircuitValidationError::MultiplyDrivenWire { wire: 3, gate: 1 }));
+
+        // Writing to an input wire also drives it twice
+        let errors = validate("2 3\n2 1 1\n1 1\n\n2 1 0 1 0 AND\n2 1 0 1 2 XOR\n").unwrap_err();
+        assert!(errors.contains(&CircuitValidationError::MultiplyDrivenWire { wire: 0, gate: 0 }));
+    }
+
+    #[test]
+    fn test_undriven_outputs_and_unused_wires() {
+        // Wire 3 is written but never read, while output wire 4 is never written
+        let errors = validate("1 5\n2 1 1\n1 1\n\n2 1 0 1 3 AND\n").unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                CircuitValidationError::UnusedWire { wire: 2 },
+                CircuitValidationError::UnusedWire { wire: 3 },
+                CircuitValidationError::UndrivenOutput { wire: 4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_circuits_are_reported() {
+        let text = "2 4\n2 1 1\n1 1\n\n2 1 0 1 2 AND\n1 1 2 3 INV\n";
+        let mut circuit = BristolCircuit::from_str(text, &TEST_SECRET).unwrap();
+        assert_eq!(circuit.validate(), Ok(()));
+
+        // A gate pointing past the circuit's wires
+        let last_wire = circuit.wires.pop().unwrap();
+        assert!(matches!(
+            circuit.validate().unwrap_err().as_slice(),
+            [CircuitValidationError::HeaderMismatch { .. }]
+        ));
+        circuit.wires.push(last_wire);
+
+        circuit.input_wire_sizes = vec![usize::MAX, 1];
+        assert!(circuit.validate().unwrap_err().contains(
+            &CircuitValidationError::HeaderMismatch {
+                reason: "input and output wire sizes overflow".to_string()
+            }
+        ));
+    }
+}
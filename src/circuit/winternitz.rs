@@ -0,0 +1,444 @@
+use bitcoin::{
+    hashes::{
+        hmac::{Hmac, HmacEngine},
+        sha256, Hash, HashEngine,
+    },
+    opcodes::all::{
+        OP_2DROP, OP_ADD, OP_BOOLOR, OP_CHECKSIG, OP_DUP, OP_EQUALVERIFY, OP_FROMALTSTACK,
+        OP_NUMNOTEQUAL, OP_PICK, OP_ROLL, OP_SHA256, OP_SWAP, OP_TOALTSTACK, OP_TUCK, OP_VERIFY,
+        OP_WITHIN,
+    },
+    script::Builder,
+    ScriptBuf, XOnlyPublicKey,
+};
+use serde::{Deserialize, Serialize};
+
+use super::wire::{HashValue, PreimageValue, WireSecret};
+
+/// Bits committed to by a single Winternitz digit
+pub const DIGIT_BITS: usize = 4;
+/// Largest digit value, and the length of every hash chain
+pub const DIGIT_MAX: u32 = (1 << DIGIT_BITS) - 1;
+
+const WORD_PREIMAGE_TAG: &[u8] = b"bitvm-demo/word-preimage";
+
+/// Width of a multi-bit wire
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordSize {
+    U8,
+    U32,
+}
+
+impl WordSize {
+    pub fn bits(&self) -> usize {
+        match self {
+            WordSize::U8 => 8,
+            WordSize::U32 => 32,
+        }
+    }
+
+    pub fn mask(&self) -> u32 {
+        u32::MAX >> (32 - self.bits())
+    }
+
+    /// Number of digits holding the value
+    pub fn message_digits(&self) -> usize {
+        self.bits() / DIGIT_BITS
+    }
+
+    /// Number of digits holding the checksum, enough for every digit being 0
+    pub fn checksum_digits(&self) -> usize {
+        let max_checksum = self.message_digits() as u32 * DIGIT_MAX;
+        (max_checksum.ilog2() as usize) / DIGIT_BITS + 1
+    }
+
+    pub fn total_digits(&self) -> usize {
+        self.message_digits() + self.checksum_digits()
+    }
+}
+
+fn hash_chain(value: HashValue, times: u32) -> HashValue {
+    (0..times).fold(value, |v, _| sha256::Hash::hash(&v).to_byte_array())
+}
+
+/// Derives the start of the hash chain of one digit, the same way wire preimages are derived
+fn derive_digit_secret(secret: &WireSecret, index: usize, digit: usize) -> PreimageValue {
+    let mut engine = HmacEngine::<sha256::Hash>::new(secret);
+    engine.input(WORD_PREIMAGE_TAG);
+    engine.input(&(index as u64).to_be_bytes());
+    engine.input(&(digit as u64).to_be_bytes());
+    Hmac::<sha256::Hash>::from_engine(engine).to_byte_array()
+}
+
+/// Message digits of `value`, least significant first, followed by the checksum digits. The
+/// checksum is the sum of `DIGIT_MAX - digit`, so raising any message digit lowers it, and
+/// revealing a larger value than the one signed would need a hash preimage.
+pub fn word_digits(size: WordSize, value: u32) -> Vec<u32> {
+    let mut digits = (0..size.message_digits())
+        .map(|i| (value >> (i * DIGIT_BITS)) & DIGIT_MAX)
+        .collect::<Vec<u32>>();
+    let checksum = digits.iter().map(|d| DIGIT_MAX - d).sum::<u32>();
+    digits.extend((0..size.checksum_digits()).map(|i| (checksum >> (i * DIGIT_BITS)) & DIGIT_MAX));
+    digits
+}
+
+fn encode_digit(digit: u32) -> Vec<u8> {
+    // Minimal script number encoding, zero is the empty vector
+    if digit == 0 {
+        vec![]
+    } else {
+        vec![digit as u8]
+    }
+}
+
+fn decode_digit(item: &[u8]) -> Option<u32> {
+    match item {
+        [] => Some(0),
+        [digit] if *digit != 0 && *digit as u32 <= DIGIT_MAX => Some(*digit as u32),
+        _ => None,
+    }
+}
+
+/// A multi-bit wire committed to with a Winternitz one time signature. Each digit has a hash
+/// chain of length `DIGIT_MAX`, the public key is the end of the chain and committing to digit
+/// `v` reveals the chain value `v` steps from its start.
+#[derive(Clone)]
+pub struct WordWire {
+    pub index: usize,
+    pub size: WordSize,
+    /// One per message digit followed by one per checksum digit
+    pub public_keys: Vec<HashValue>,
+    /// Start of every hash chain, only known to the prover
+    pub secrets: Option<Vec<PreimageValue>>,
+    pub value: Option<u32>,
+}
+
+impl WordWire {
+    pub fn new(index: usize, size: WordSize, secret: &WireSecret) -> Self {
+        let secrets = (0..size.total_digits())
+            .map(|digit| derive_digit_secret(secret, index, digit))
+            .collect::<Vec<PreimageValue>>();
+        WordWire {
+            index,
+            size,
+            public_keys: secrets.iter().map(|s| hash_chain(*s, DIGIT_MAX)).collect(),
+            secrets: Some(secrets),
+            value: None,
+        }
+    }
+
+    /// A word wire known only by its public keys, as the verifier sees it
+    pub fn from_public_keys(index: usize, size: WordSize, public_keys: Vec<HashValue>) -> Self {
+        assert_eq!(
+            public_keys.len(),
+            size.total_digits(),
+            "wrong number of public keys for the word size"
+        );
+        WordWire {
+            index,
+            size,
+            public_keys,
+            secrets: None,
+            value: None,
+        }
+    }
+
+    /// Witness items committing to the wire's value, in the order they're pushed. Each digit is
+    /// its chain value followed by the digit itself, with the first digit ending up on top.
+    pub fn create_commitment_witness(&self) -> Vec<Vec<u8>> {
+        let secrets = self.secrets.as_ref().expect("secrets are not set");
+        let value = self.value.expect("value is not set");
+        let digits = word_digits(self.size, value);
+        let mut witness = Vec::new();
+        for (secret, digit) in secrets.iter().zip(digits).rev() {
+            witness.push(hash_chain(*secret, digit).to_vec());
+            witness.push(encode_digit(digit));
+        }
+        witness
+    }
+
+    /// Checks a commitment produced by `create_commitment_witness` against the public keys,
+    /// returning the committed value
+    pub fn decode_commitment(&self, witness: &[Vec<u8>]) -> Option<u32> {
+        if witness.len() != 2 * self.size.total_digits() {
+            return None;
+        }
+        let mut digits = Vec::new();
+        for (pair, public_key) in witness.rchunks(2).zip(self.public_keys.iter()) {
+            let digit = decode_digit(&pair[1])?;
+            let chain_value: HashValue = pair[0].as_slice().try_into().ok()?;
+            if hash_chain(chain_value, DIGIT_MAX - digit) != *public_key {
+                return None;
+            }
+            digits.push(digit);
+        }
+        let value = digits[..self.size.message_digits()]
+            .iter()
+            .rev()
+            .fold(0, |acc, digit| (acc << DIGIT_BITS) | digit);
+        (word_digits(self.size, value) == digits).then_some(value)
+    }
+}
+
+/// Verifies a word commitment on top of the stack, leaving its message digits with the least
+/// significant one on top. Every digit is checked by hashing its chain value to the end and
+/// picking the element `digit` steps from the public key, then the checksum is recomputed.
+pub fn add_word_commitment_script(
+    public_keys: &[HashValue],
+    size: WordSize,
+    mut builder: Builder,
+) -> Builder {
+    assert_eq!(public_keys.len(), size.total_digits());
+    for public_key in public_keys {
+        builder = builder
+            .push_opcode(OP_DUP)
+            .push_int(0)
+            .push_int(DIGIT_MAX as i64 + 1)
+            .push_opcode(OP_WITHIN)
+            .push_opcode(OP_VERIFY)
+            .push_opcode(OP_TOALTSTACK);
+        for _ in 0..DIGIT_MAX {
+            builder = builder.push_opcode(OP_DUP).push_opcode(OP_SHA256);
+        }
+        builder = builder
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_DUP)
+            .push_opcode(OP_TOALTSTACK)
+            .push_opcode(OP_PICK)
+            .push_slice(public_key)
+            .push_opcode(OP_EQUALVERIFY);
+        for _ in 0..DIGIT_MAX.div_ceil(2) {
+            builder = builder.push_opcode(OP_2DROP);
+        }
+    }
+
+    // The altstack holds the digits with the last checksum digit on top, rebuild the checksum
+    builder = builder.push_opcode(OP_FROMALTSTACK);
+    for _ in 1..size.checksum_digits() {
+        for _ in 0..DIGIT_BITS {
+            builder = builder.push_opcode(OP_DUP).push_opcode(OP_ADD);
+        }
+        builder = builder.push_opcode(OP_FROMALTSTACK).push_opcode(OP_ADD);
+    }
+    // Bring the message digits back while adding them to the checksum
+    for _ in 0..size.message_digits() {
+        builder = builder
+            .push_opcode(OP_FROMALTSTACK)
+            .push_opcode(OP_TUCK)
+            .push_opcode(OP_ADD);
+    }
+    builder
+        .push_int(size.message_digits() as i64 * DIGIT_MAX as i64)
+        .push_opcode(OP_EQUALVERIFY)
+}
+
+/// Lets the verifier take the funds if the prover committed to two different values for the
+/// same word. Checksums are verified for both commitments, so a commitment can't be forged by
+/// hashing a revealed digit further.
+pub fn generate_word_equivocation_script(
+    public_keys: &[HashValue],
+    size: WordSize,
+    verifier_pk: XOnlyPublicKey,
+) -> ScriptBuf {
+    let n = size.message_digits();
+    let mut builder = add_word_commitment_script(public_keys, size, Builder::new());
+    for _ in 0..n {
+        builder = builder.push_opcode(OP_TOALTSTACK);
+    }
+    builder = add_word_commitment_script(public_keys, size, builder);
+    for _ in 0..n {
+        builder = builder.push_opcode(OP_FROMALTSTACK);
+    }
+    // Stack is b_{n-1}..b_0 a_{n-1}..a_0, compare the digits pairwise from the top
+    for i in 0..n {
+        // a_i is on top, b_i sits below the remaining digits of a and the running result
+        let depth = (n - i) + (i > 0) as usize;
+        builder = builder
+            .push_int(depth as i64)
+            .push_opcode(OP_ROLL)
+            .push_opcode(OP_NUMNOTEQUAL);
+        if i > 0 {
+            builder = builder.push_opcode(OP_BOOLOR);
+        }
+        if i + 1 < n {
+            // Keep the running result above the next digit of a
+            builder = builder.push_opcode(OP_SWAP);
+        }
+    }
+    builder
+        .push_opcode(OP_VERIFY)
+        .push_x_only_key(&verifier_pk)
+        .push_opcode(OP_CHECKSIG)
+        .into_script()
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        opcodes::all::{OP_EQUALVERIFY, OP_VERIFY},
+        script::Builder,
+        Sequence,
+    };
+
+    use crate::{
+        actor::{Actor, ActorType},
+        circuit::tests::TEST_SECRET,
+        transactions::interpreter::{execute_script, spend_leaf, ScriptError},
+    };
+
+    use super::{
+        add_word_commitment_script, encode_digit, generate_word_equivocation_script, word_digits,
+        WordSize, WordWire, DIGIT_MAX,
+    };
+
+    #[test]
+    fn test_digit_counts() {
+        assert_eq!(WordSize::U8.message_digits(), 2);
+        assert_eq!(WordSize::U8.checksum_digits(), 2);
+        assert_eq!(WordSize::U32.message_digits(), 8);
+        assert_eq!(WordSize::U32.checksum_digits(), 2);
+    }
+
+    #[test]
+    fn test_word_digits() {
+        let digits = word_digits(WordSize::U32, 0x1234_abcd);
+        assert_eq!(digits[..8], [0xd, 0xc, 0xb, 0xa, 4, 3, 2, 1]);
+        let checksum = digits[..8].iter().map(|d| DIGIT_MAX - d).sum::<u32>();
+        assert_eq!(digits[8] + (digits[9] << 4), checksum);
+    }
+
+    #[test]
+    fn test_commitment_round_trip() {
+        for (size, value) in [
+            (WordSize::U8, 0xa7),
+            (WordSize::U32, 0xdead_beef),
+            (WordSize::U32, 0),
+        ] {
+            let mut wire = WordWire::new(3, size, &TEST_SECRET);
+            wire.value = Some(value);
+            let witness = wire.create_commitment_witness();
+            assert_eq!(witness.len(), 2 * size.total_digits());
+
+            let verifier_wire = WordWire::from_public_keys(3, size, wire.public_keys.clone());
+            assert_eq!(verifier_wire.decode_commitment(&witness), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_forged_commitment_is_rejected() {
+        let mut wire = WordWire::new(0, WordSize::U8, &TEST_SECRET);
+        wire.value = Some(0x21);
+        let mut witness = wire.create_commitment_witness();
+
+        // Hashing the lowest digit's chain value once more is a valid chain value for digit
+        // 2, but the checksum no longer matches
+        let last = witness.len() - 1;
+        let chain_value: [u8; 32] = witness[last - 1].clone().try_into().unwrap();
+        witness[last - 1] = super::hash_chain(chain_value, 1).to_vec();
+        witness[last] = vec![2];
+        assert_eq!(wire.decode_commitment(&witness), None);
+
+        // Other wires' keys don't accept the commitment either
+        let other = WordWire::new(1, WordSize::U8, &TEST_SECRET);
+        assert_eq!(
+            other.decode_commitment(&wire.create_commitment_witness()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_commitment_script() {
+        for (size, value) in [
+            (WordSize::U8, 0xa7),
+            (WordSize::U8, 0),
+            (WordSize::U8, 0xff),
+            (WordSize::U32, 0xdead_beef),
+        ] {
+            let mut wire = WordWire::new(3, size, &TEST_SECRET);
+            wire.value = Some(value);
+            let script =
+                add_word_commitment_script(&wire.public_keys, size, Builder::new()).into_script();
+
+            // The message digits are left with the least significant one on top
+            let digits = word_digits(size, value)[..size.message_digits()]
+                .iter()
+                .rev()
+                .map(|digit| encode_digit(*digit))
+                .collect::<Vec<Vec<u8>>>();
+            assert_eq!(
+                execute_script(&script, wire.create_commitment_witness(), None),
+                Ok(digits)
+            );
+        }
+    }
+
+    #[test]
+    fn test_commitment_script_rejects_forgeries() {
+        let mut wire = WordWire::new(0, WordSize::U8, &TEST_SECRET);
+        wire.value = Some(0x21);
+        let script = add_word_commitment_script(&wire.public_keys, WordSize::U8, Builder::new())
+            .into_script();
+        let witness = wire.create_commitment_witness();
+        let last = witness.len() - 1;
+
+        // Raising a digit by hashing its chain value is caught by the checksum
+        let mut forged = witness.clone();
+        let chain_value: [u8; 32] = forged[last - 1].clone().try_into().unwrap();
+        forged[last - 1] = super::hash_chain(chain_value, 1).to_vec();
+        forged[last] = vec![2];
+        assert_eq!(
+            execute_script(&script, forged, None),
+            Err(ScriptError::VerifyFailed(OP_EQUALVERIFY))
+        );
+
+        // Lowering a digit would need a hash preimage, claiming it fails the chain check
+        let mut forged = witness.clone();
+        forged[last] = vec![];
+        assert_eq!(
+            execute_script(&script, forged, None),
+            Err(ScriptError::VerifyFailed(OP_EQUALVERIFY))
+        );
+
+        // Digits past the end of the chain are rejected before any hashing
+        let mut forged = witness;
+        forged[last] = vec![16];
+        assert_eq!(
+            execute_script(&script, forged, None),
+            Err(ScriptError::VerifyFailed(OP_VERIFY))
+        );
+    }
+
+    #[test]
+    fn test_word_equivocation_script() {
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+        for size in [WordSize::U8, WordSize::U32] {
+            let mut wire = WordWire::new(5, size, &TEST_SECRET);
+            let script = generate_word_equivocation_script(&wire.public_keys, size, verifier.pk);
+            let mut commit = |value| {
+                wire.value = Some(value);
+                wire.create_commitment_witness()
+            };
+            let (first, second, same) = (commit(0x12), commit(0x13), commit(0x12));
+
+            assert_eq!(
+                spend_leaf(
+                    script.clone(),
+                    [first.clone(), second].concat(),
+                    &[&verifier],
+                    Sequence::ENABLE_RBF_NO_LOCKTIME
+                ),
+                Ok(())
+            );
+            // Committing to the same value twice isn't equivocation
+            assert_eq!(
+                spend_leaf(
+                    script,
+                    [first, same].concat(),
+                    &[&verifier],
+                    Sequence::ENABLE_RBF_NO_LOCKTIME
+                ),
+                Err(ScriptError::VerifyFailed(OP_VERIFY))
+            );
+        }
+    }
+}
@@ -1,14 +1,30 @@
-use bitcoin::{
-    hashes::{sha256, Hash},
-    key::rand::{rngs::StdRng, SeedableRng},
+use std::fmt;
+
+use bitcoin::hashes::{
+    hmac::{Hmac, HmacEngine},
+    sha256, Hash, HashEngine,
 };
-use bitcoincore_rpc::bitcoin::key::rand::{self, Rng};
 use serde::{Deserialize, Serialize};
 
 pub type HashValue = [u8; 32];
 pub type PreimageValue = [u8; 32];
+/// The prover's master secret that every wire preimage is derived from
+pub type WireSecret = [u8; 32];
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+const WIRE_PREIMAGE_TAG: &[u8] = b"bitvm-demo/wire-preimage";
+
+/// Derives the preimage committing to `bit` on the wire at `index`. This is
+/// HMAC-SHA256(secret, tag || index || bit), so preimages are unique per wire and bit, and
+/// anyone holding the secret can reproduce them.
+pub fn derive_wire_preimage(secret: &WireSecret, index: usize, bit: bool) -> PreimageValue {
+    let mut engine = HmacEngine::<sha256::Hash>::new(secret);
+    engine.input(WIRE_PREIMAGE_TAG);
+    engine.input(&(index as u64).to_be_bytes());
+    engine.input(&[bit as u8]);
+    Hmac::<sha256::Hash>::from_engine(engine).to_byte_array()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct HashTuple {
     pub zero: HashValue,
     pub one: HashValue,
@@ -28,15 +44,25 @@ pub struct Wire {
     pub selector: Option<bool>,
 }
 
-impl Wire {
-    pub fn new(index: usize, seed: Option<u64>) -> Self {
-        let mut rng = match seed {
-            Some(seed) => StdRng::seed_from_u64(seed),
-            None => StdRng::from_entropy(),
-        };
+// Preimages are secrets until revealed on chain, so only whether they're known is printed
+impl fmt::Debug for Wire {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let known = self
+            .preimages
+            .map(|preimages| (preimages.zero.is_some(), preimages.one.is_some()));
+        f.debug_struct("Wire")
+            .field("index", &self.index)
+            .field("hashes", &self.hashes)
+            .field("known_preimages", &known)
+            .field("selector", &self.selector)
+            .finish()
+    }
+}
 
-        let preimage1: PreimageValue = rng.gen();
-        let preimage2: PreimageValue = rng.gen();
+impl Wire {
+    pub fn new(index: usize, secret: &WireSecret) -> Self {
+        let preimage1 = derive_wire_preimage(secret, index, false);
+        let preimage2 = derive_wire_preimage(secret, index, true);
 
         let hash1 = sha256::Hash::hash(&preimage1).to_byte_array();
         let hash2 = sha256::Hash::hash(&preimage2).to_byte_array();
@@ -55,6 +81,16 @@ impl Wire {
         }
     }
 
+    /// A wire known only by its commitment, as the verifier sees it
+    pub fn from_hashes(index: usize, hashes: HashTuple) -> Self {
+        Wire {
+            preimages: None,
+            hashes,
+            index: Some(index),
+            selector: None,
+        }
+    }
+
     pub fn get_hash_pair(&self) -> HashTuple {
         self.hashes
     }
@@ -75,30 +111,124 @@ impl Wire {
         }
     }
 
-    pub fn add_preimage(&mut self, preimage: PreimageValue) -> Option<Wire> {
-        let hash = sha256::Hash::hash(&preimage).to_byte_array();
+    /// The bit `preimage` opens on this wire, if it matches either hash
+    pub fn bit_of_preimage(&self, preimage: &PreimageValue) -> Option<bool> {
+        let hash = sha256::Hash::hash(preimage).to_byte_array();
         if hash == self.hashes.zero {
-            self.preimages = Some(PreimageTuple {
-                zero: Some(preimage),
-                one: match self.preimages {
-                    Some(cur) => cur.one,
-                    None => None,
-                },
-            });
+            Some(false)
         } else if hash == self.hashes.one {
-            self.preimages = Some(PreimageTuple {
-                zero: match self.preimages {
-                    Some(cur) => cur.zero,
-                    None => None,
-                },
-                one: Some(preimage),
-            });
+            Some(true)
         } else {
-            panic!("preimage does not match either hash");
+            None
         }
-        if self.preimages.unwrap().zero.is_some() && self.preimages.unwrap().one.is_some() {
-            return Some(self.clone());
+    }
+
+    /// Records a preimage revealed for this wire. Preimages matching neither hash are rejected
+    /// without touching the wire, as they come from data the prover controls.
+    pub fn add_preimage(&mut self, preimage: PreimageValue) -> PreimageOutcome {
+        let bit = match self.bit_of_preimage(&preimage) {
+            Some(bit) => bit,
+            None => return PreimageOutcome::InvalidPreimage,
+        };
+        let mut preimages = self.preimages.unwrap_or(PreimageTuple {
+            zero: None,
+            one: None,
+        });
+        let known = if bit {
+            preimages.one.replace(preimage).is_some()
+        } else {
+            preimages.zero.replace(preimage).is_some()
+        };
+        self.preimages = Some(preimages);
+        if preimages.zero.is_some() && preimages.one.is_some() {
+            PreimageOutcome::Contradiction(self.clone())
+        } else if known {
+            PreimageOutcome::AlreadyKnown
+        } else {
+            PreimageOutcome::NewBit
+        }
+    }
+}
+
+/// What recording revealed preimages taught the verifier
+#[derive(Debug, Clone)]
+pub enum PreimageOutcome {
+    /// A value that wasn't known before was revealed
+    NewBit,
+    /// Only values that were already known were revealed
+    AlreadyKnown,
+    /// Both preimages of this wire are known, so the prover revealed both of its values
+    Contradiction(Wire),
+    /// A preimage matches neither hash of its wire
+    InvalidPreimage,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{PreimageOutcome, Wire};
+
+    #[test]
+    fn test_wire_preimages_are_unique() {
+        let secret = [7; 32];
+        let mut preimages = HashSet::new();
+        for i in 0..1000 {
+            let preimage_tuple = Wire::new(i, &secret).preimages.unwrap();
+            assert!(preimages.insert(preimage_tuple.zero.unwrap()));
+            assert!(preimages.insert(preimage_tuple.one.unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_wire_preimages_are_reproducible() {
+        let wire = Wire::new(42, &[1; 32]);
+        let same_secret = Wire::new(42, &[1; 32]);
+        let other_secret = Wire::new(42, &[2; 32]);
+
+        assert_eq!(wire.hashes.zero, same_secret.hashes.zero);
+        assert_eq!(wire.hashes.one, same_secret.hashes.one);
+        assert_ne!(wire.hashes.zero, other_secret.hashes.zero);
+        assert_ne!(wire.hashes.one, other_secret.hashes.one);
+    }
+
+    #[test]
+    fn test_add_preimage_outcomes() {
+        let prover_wire = Wire::new(3, &[5; 32]);
+        let preimages = prover_wire.preimages.unwrap();
+        let mut wire = Wire::from_hashes(3, prover_wire.hashes);
+
+        assert!(matches!(
+            wire.add_preimage(preimages.one.unwrap()),
+            PreimageOutcome::NewBit
+        ));
+        assert!(matches!(
+            wire.add_preimage(preimages.one.unwrap()),
+            PreimageOutcome::AlreadyKnown
+        ));
+        assert!(matches!(
+            wire.add_preimage([0; 32]),
+            PreimageOutcome::InvalidPreimage
+        ));
+        assert!(wire.preimages.unwrap().zero.is_none());
+
+        match wire.add_preimage(preimages.zero.unwrap()) {
+            PreimageOutcome::Contradiction(contradiction) => {
+                assert_eq!(contradiction.index, Some(3));
+                assert!(contradiction.preimages.unwrap().zero.is_some());
+            }
+            outcome => panic!("expected a contradiction, got {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_debug_hides_preimages() {
+        let wire = Wire::new(3, &[5; 32]);
+        let preimages = wire.preimages.unwrap();
+        let debug = format!("{:?}", wire);
+        assert!(debug.contains("known_preimages: Some((true, true))"));
+        for preimage in [preimages.zero.unwrap(), preimages.one.unwrap()] {
+            assert!(!debug.contains(&format!("{:?}", preimage)));
         }
-        None
     }
 }
@@ -0,0 +1,413 @@
+use std::sync::{Arc, Mutex};
+
+use bitcoin::{
+    opcodes::all::{
+        OP_2DROP, OP_ADD, OP_DROP, OP_DUP, OP_ENDIF, OP_EQUALVERIFY, OP_FROMALTSTACK,
+        OP_GREATERTHANOREQUAL, OP_IF, OP_NUMNOTEQUAL, OP_ROLL, OP_ROT, OP_SHA256, OP_SUB, OP_SWAP,
+        OP_TOALTSTACK, OP_TUCK,
+    },
+    script::Builder,
+    ScriptBuf,
+};
+
+use super::{
+    winternitz::{add_word_commitment_script, WordSize, WordWire, DIGIT_BITS, DIGIT_MAX},
+    wire::{HashValue, PreimageValue},
+};
+
+pub type SafeWordWire = Arc<Mutex<WordWire>>;
+
+/// Operations a word gate applies to its two input words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordGateType {
+    /// Addition modulo 2^bits
+    Add,
+    Xor,
+}
+
+/// Tracks where the decoded digits sit on the stack while a response script is generated, so
+/// they can be rolled to the top in whatever order the operation needs them
+struct DigitStack {
+    /// (word, digit) of every decoded digit, bottom first
+    digits: Vec<(usize, usize)>,
+    /// Temporary values above the digits
+    temps: usize,
+}
+
+impl DigitStack {
+    fn roll(&mut self, word: usize, digit: usize, builder: Builder) -> Builder {
+        let position = self
+            .digits
+            .iter()
+            .position(|d| *d == (word, digit))
+            .expect("digit is not on the stack");
+        let depth = self.digits.len() - 1 - position + self.temps;
+        self.digits.remove(position);
+        self.temps += 1;
+        match depth {
+            0 => builder,
+            1 => builder.push_opcode(OP_SWAP),
+            2 => builder.push_opcode(OP_ROT),
+            _ => builder.push_int(depth as i64).push_opcode(OP_ROLL),
+        }
+    }
+}
+
+/// A gate over u8 or u32 words. The whole operation is checked in one response leaf, where a
+/// bit level circuit would need a leaf for every gate of e.g. an adder.
+pub struct WordGate {
+    pub gate_type: WordGateType,
+    pub size: WordSize,
+    pub input_wires: Vec<SafeWordWire>,
+    pub output_wires: Vec<SafeWordWire>,
+}
+
+impl WordGate {
+    pub fn new(
+        gate_type: WordGateType,
+        input_wires: Vec<SafeWordWire>,
+        output_wires: Vec<SafeWordWire>,
+    ) -> Self {
+        assert_eq!(input_wires.len(), 2, "word gates take two inputs");
+        assert_eq!(output_wires.len(), 1, "word gates have one output");
+        let size = output_wires[0].lock().unwrap().size;
+        for wire in input_wires.iter() {
+            assert_eq!(wire.lock().unwrap().size, size, "word sizes must match");
+        }
+        WordGate {
+            gate_type,
+            size,
+            input_wires,
+            output_wires,
+        }
+    }
+
+    pub fn run_gate_on_inputs(&self, inputs: &[u32]) -> u32 {
+        assert!(inputs.len() == 2);
+        let output = match self.gate_type {
+            WordGateType::Add => inputs[0].wrapping_add(inputs[1]),
+            WordGateType::Xor => inputs[0] ^ inputs[1],
+        };
+        output & self.size.mask()
+    }
+
+    pub fn evaluate(&mut self) {
+        let inputs = self
+            .input_wires
+            .iter()
+            .map(|wire| wire.lock().unwrap().value.expect("input value is not set"))
+            .collect::<Vec<u32>>();
+        self.output_wires[0].lock().unwrap().value = Some(self.run_gate_on_inputs(&inputs));
+    }
+
+    /// Same layout as the bit gates' response scripts: the challenge hashlock, then the output
+    /// and input commitments, then a check that the output is the operation on the inputs
+    pub fn create_response_script(&self, lock_hash: HashValue) -> ScriptBuf {
+        let n = self.size.message_digits();
+        let mut builder = Builder::new()
+            .push_opcode(OP_SHA256)
+            .push_slice(lock_hash)
+            .push_opcode(OP_EQUALVERIFY);
+        for wire in [&self.output_wires[0], &self.input_wires[1]] {
+            builder =
+                add_word_commitment_script(&wire.lock().unwrap().public_keys, self.size, builder);
+            for _ in 0..n {
+                builder = builder.push_opcode(OP_TOALTSTACK);
+            }
+        }
+        builder = add_word_commitment_script(
+            &self.input_wires[0].lock().unwrap().public_keys,
+            self.size,
+            builder,
+        );
+        for _ in 0..2 * n {
+            builder = builder.push_opcode(OP_FROMALTSTACK);
+        }
+
+        // Words are 0 = first input, 1 = second input, 2 = output, most significant digit at
+        // the bottom of each
+        let mut stack = DigitStack {
+            digits: (0..3)
+                .flat_map(|word| (0..n).rev().map(move |digit| (word, digit)))
+                .collect(),
+            temps: 0,
+        };
+        for digit in 0..n {
+            builder = match self.gate_type {
+                WordGateType::Add => add_digit_script(&mut stack, digit, n, builder),
+                WordGateType::Xor => xor_digit_script(&mut stack, digit, builder),
+            };
+        }
+        builder.into_script()
+    }
+
+    /// Commitments to the current input and output values followed by the hashlock preimage,
+    /// in the order `create_response_script` expects them
+    pub fn create_response_witness(&self, hashlock_preimage: PreimageValue) -> Vec<Vec<u8>> {
+        let mut witness = Vec::new();
+        for wire in self.input_wires.iter().chain(self.output_wires.iter()) {
+            witness.extend(wire.lock().unwrap().create_commitment_witness());
+        }
+        witness.push(hashlock_preimage.to_vec());
+        witness
+    }
+}
+
+/// Adds one digit of the inputs with the carry from the previous digit, checks it against the
+/// output digit and leaves the new carry on the stack
+fn add_digit_script(stack: &mut DigitStack, digit: usize, n: usize, builder: Builder) -> Builder {
+    let builder = stack.roll(0, digit, builder);
+    let mut builder = stack.roll(1, digit, builder).push_opcode(OP_ADD);
+    stack.temps -= 1;
+    if digit > 0 {
+        builder = builder.push_opcode(OP_ADD);
+        stack.temps -= 1;
+    }
+    builder = builder
+        .push_opcode(OP_DUP)
+        .push_int(DIGIT_MAX as i64 + 1)
+        .push_opcode(OP_GREATERTHANOREQUAL)
+        .push_opcode(OP_DUP)
+        .push_opcode(OP_TOALTSTACK)
+        .push_opcode(OP_IF)
+        .push_int(DIGIT_MAX as i64 + 1)
+        .push_opcode(OP_SUB)
+        .push_opcode(OP_ENDIF);
+    builder = stack.roll(2, digit, builder).push_opcode(OP_EQUALVERIFY);
+    stack.temps -= 2;
+    builder = builder.push_opcode(OP_FROMALTSTACK);
+    if digit + 1 == n {
+        // The final carry falls off the word
+        builder.push_opcode(OP_DROP)
+    } else {
+        stack.temps += 1;
+        builder
+    }
+}
+
+/// Xors one digit of the inputs bit by bit, as script has no bitwise operations, and checks it
+/// against the output digit
+fn xor_digit_script(stack: &mut DigitStack, digit: usize, builder: Builder) -> Builder {
+    // Stack is result a b, with bits taken off a and b from the most significant down
+    let builder = builder.push_int(0);
+    stack.temps += 1;
+    let builder = stack.roll(0, digit, builder);
+    let mut builder = stack.roll(1, digit, builder);
+    for bit in (0..DIGIT_BITS).rev() {
+        let weight = 1i64 << bit;
+        builder = builder
+            .push_opcode(OP_SWAP)
+            .push_opcode(OP_DUP)
+            .push_int(weight)
+            .push_opcode(OP_GREATERTHANOREQUAL)
+            .push_opcode(OP_TUCK)
+            .push_opcode(OP_IF)
+            .push_int(weight)
+            .push_opcode(OP_SUB)
+            .push_opcode(OP_ENDIF)
+            // result b bit_a a
+            .push_opcode(OP_ROT)
+            .push_opcode(OP_DUP)
+            .push_int(weight)
+            .push_opcode(OP_GREATERTHANOREQUAL)
+            .push_opcode(OP_TUCK)
+            .push_opcode(OP_IF)
+            .push_int(weight)
+            .push_opcode(OP_SUB)
+            .push_opcode(OP_ENDIF)
+            // result bit_a a bit_b b
+            .push_int(3)
+            .push_opcode(OP_ROLL)
+            .push_int(2)
+            .push_opcode(OP_ROLL)
+            .push_opcode(OP_NUMNOTEQUAL)
+            // result a b (bit_a != bit_b)
+            .push_opcode(OP_IF)
+            .push_int(2)
+            .push_opcode(OP_ROLL)
+            .push_int(weight)
+            .push_opcode(OP_ADD)
+            .push_opcode(OP_ROT)
+            .push_opcode(OP_ROT)
+            .push_opcode(OP_ENDIF);
+    }
+    builder = builder.push_opcode(OP_2DROP);
+    stack.temps -= 2;
+    builder = stack.roll(2, digit, builder).push_opcode(OP_EQUALVERIFY);
+    stack.temps -= 2;
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bitcoin::{
+        hashes::{sha256, Hash},
+        key::Secp256k1,
+        opcodes::all::OP_EQUALVERIFY,
+        taproot::LeafVersion,
+        Sequence,
+    };
+
+    use crate::{
+        actor::{Actor, ActorType},
+        circuit::{
+            tests::TEST_SECRET,
+            winternitz::{WordSize, WordWire},
+        },
+        transactions::{
+            generate_word_gate_response_script, generate_word_response_address_and_info,
+            interpreter::{execute_script, spend_leaf, ScriptError},
+        },
+    };
+
+    use super::{WordGate, WordGateType};
+
+    fn gate(gate_type: WordGateType, size: WordSize) -> WordGate {
+        let wire = |i| Arc::new(Mutex::new(WordWire::new(i, size, &TEST_SECRET)));
+        WordGate::new(gate_type, vec![wire(0), wire(1)], vec![wire(2)])
+    }
+
+    #[test]
+    fn test_word_gate_evaluation() {
+        let mut add = gate(WordGateType::Add, WordSize::U32);
+        add.input_wires[0].lock().unwrap().value = Some(u32::MAX);
+        add.input_wires[1].lock().unwrap().value = Some(5);
+        add.evaluate();
+        assert_eq!(add.output_wires[0].lock().unwrap().value, Some(4));
+
+        let xor = gate(WordGateType::Xor, WordSize::U8);
+        assert_eq!(xor.run_gate_on_inputs(&[0xf0, 0x3c]), 0xcc);
+        let add = gate(WordGateType::Add, WordSize::U8);
+        assert_eq!(add.run_gate_on_inputs(&[0xff, 0x02]), 0x01);
+    }
+
+    #[test]
+    fn test_response_witness_layout() {
+        let mut add = gate(WordGateType::Add, WordSize::U32);
+        add.input_wires[0].lock().unwrap().value = Some(7);
+        add.input_wires[1].lock().unwrap().value = Some(9);
+        add.evaluate();
+
+        let witness = add.create_response_witness([1; 32]);
+        let word_items = 2 * WordSize::U32.total_digits();
+        assert_eq!(witness.len(), 3 * word_items + 1);
+        assert_eq!(witness.last().unwrap(), &vec![1; 32]);
+
+        let output = add.output_wires[0].lock().unwrap();
+        assert_eq!(
+            output.decode_commitment(&witness[2 * word_items..3 * word_items]),
+            Some(16)
+        );
+    }
+
+    #[test]
+    fn test_single_leaf_replaces_bit_gates() {
+        // A 32 bit ripple carry adder is five bit gates per bit, each its own leaf, while the
+        // word gate script still fits comfortably in one leaf
+        let script = gate(WordGateType::Add, WordSize::U32).create_response_script([0; 32]);
+        assert!(script.len() < 10_000);
+        let script = gate(WordGateType::Xor, WordSize::U32).create_response_script([0; 32]);
+        assert!(script.len() < 10_000);
+    }
+
+    /// Runs the response script on a commitment to `inputs` and the claimed `output`
+    fn run_response(gate: &WordGate, inputs: [u32; 2], output: u32) -> Result<(), ScriptError> {
+        let hashlock = [3; 32];
+        let script = gate.create_response_script(sha256::Hash::hash(&hashlock).to_byte_array());
+        for (wire, value) in gate.input_wires.iter().zip(inputs) {
+            wire.lock().unwrap().value = Some(value);
+        }
+        gate.output_wires[0].lock().unwrap().value = Some(output);
+        execute_script(&script, gate.create_response_witness(hashlock), None).map(|stack| {
+            assert!(stack.is_empty(), "response script left {:?}", stack);
+        })
+    }
+
+    #[test]
+    fn test_response_scripts_over_every_digit_pair() {
+        for gate_type in [WordGateType::Add, WordGateType::Xor] {
+            let gate = gate(gate_type, WordSize::U8);
+            // Every pair of low digits, with the high digits varied so both carries show up
+            for a in 0..16 {
+                for b in 0..16 {
+                    let inputs = [a | b << 4, b | (15 - a) << 4];
+                    let output = gate.run_gate_on_inputs(&inputs);
+                    assert_eq!(run_response(&gate, inputs, output), Ok(()));
+                    for wrong in [output ^ 1, output ^ 0x10] {
+                        assert_eq!(
+                            run_response(&gate, inputs, wrong),
+                            Err(ScriptError::VerifyFailed(OP_EQUALVERIFY)),
+                            "{:?} {:?} accepted {:#x}",
+                            gate_type,
+                            inputs,
+                            wrong
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_u32_response_scripts() {
+        for gate_type in [WordGateType::Add, WordGateType::Xor] {
+            let gate = gate(gate_type, WordSize::U32);
+            for inputs in [
+                [u32::MAX, 1],
+                [0x0fff_ffff, 0x0000_0001],
+                [0xdead_beef, 0x1234_5678],
+                [0, 0],
+            ] {
+                let output = gate.run_gate_on_inputs(&inputs);
+                assert_eq!(run_response(&gate, inputs, output), Ok(()));
+                assert_eq!(
+                    run_response(&gate, inputs, output ^ 0x1000_0000),
+                    Err(ScriptError::VerifyFailed(OP_EQUALVERIFY))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_word_response_leaves() {
+        let prover = Actor::new(ActorType::Prover, Some(0));
+        let gates = [
+            gate(WordGateType::Add, WordSize::U8),
+            gate(WordGateType::Xor, WordSize::U8),
+        ];
+        let hashlocks = [[3; 32], [4; 32]];
+        let challenge_hashes =
+            hashlocks.map(|hashlock| sha256::Hash::hash(&hashlock).to_byte_array());
+        let (_, response_taproot_info) = generate_word_response_address_and_info(
+            &Secp256k1::new(),
+            &gates,
+            prover.pk,
+            &challenge_hashes,
+        );
+
+        for ((gate, hashlock), challenge_hash) in gates.iter().zip(hashlocks).zip(challenge_hashes)
+        {
+            let inputs = [0x12, 0x34];
+            for (wire, value) in gate.input_wires.iter().zip(inputs) {
+                wire.lock().unwrap().value = Some(value);
+            }
+            gate.output_wires[0].lock().unwrap().value = Some(gate.run_gate_on_inputs(&inputs));
+
+            let script = generate_word_gate_response_script(gate, &challenge_hash, prover.pk);
+            assert!(response_taproot_info
+                .control_block(&(script.clone(), LeafVersion::TapScript))
+                .is_some());
+            assert_eq!(
+                spend_leaf(
+                    script,
+                    gate.create_response_witness(hashlock),
+                    &[&prover],
+                    Sequence::ENABLE_RBF_NO_LOCKTIME
+                ),
+                Ok(())
+            );
+        }
+    }
+}
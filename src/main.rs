@@ -9,7 +9,7 @@ use bitcoin::{
     Amount, Transaction, TxOut,
 };
 use bitcoincore_rpc::RpcApi;
-use circuit::BristolCircuit;
+use circuit::{wire::WireSecret, BristolCircuit};
 use constants::WALLET_NAME;
 use transactions::{
     challenge::{build_challenge_tx, build_response_tx},
@@ -19,7 +19,7 @@ use transactions::{
 };
 use utils::{
     bitcoin_rpc::setup_client_and_fund_prover, challenge_hashes::ChallengeHashesManager,
-    conversions::number_to_bool_array, witness::fill_response_tx_with_witness_for_gate_challenge,
+    conversions::Endianness, witness::fill_response_tx_with_witness_for_gate_challenge,
 };
 
 mod actor;
@@ -30,7 +30,20 @@ mod transactions;
 mod utils;
 
 fn main() {
-    let mut circuit = BristolCircuit::from_bristol("circuits/add.txt");
+    let mut rng = rand::thread_rng();
+
+    // The prover's master secret, every wire preimage is derived from it
+    let wire_secret: WireSecret = rng.gen();
+    let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &wire_secret)
+        .unwrap_or_else(|e| panic!("Failed to load circuit: {}", e));
+    circuit
+        .validate()
+        .unwrap_or_else(|errors| panic!("Invalid circuit: {:?}", errors));
+    println!("{}", circuit.stats());
+
+    // The verifier only receives the public wire hashes, never the prover's preimages
+    let verifier_circuit = BristolCircuit::from_bristol("circuits/add.txt", &circuit.commitment())
+        .unwrap_or_else(|e| panic!("Failed to load verifier circuit: {}", e));
 
     let mut prover = Actor::new(ActorType::Prover, None);
     let mut verifier = Actor::new(ActorType::Verifier, None);
@@ -46,7 +59,6 @@ fn main() {
         Amount::from_sat(100_000),
     );
 
-    let mut rng = rand::thread_rng();
     let secp = Secp256k1::new();
     // WTF is this actually
     let bisection_length = 10;
@@ -55,7 +67,7 @@ fn main() {
     let dust_limit: u64 = 546;
 
     let (equivocation_address, equivocation_taproot_info) =
-        generate_equivocation_address_and_info(&secp, &circuit, prover.pk, verifier.pk);
+        generate_equivocation_address_and_info(&secp, &verifier_circuit, prover.pk, verifier.pk);
 
     let (response_second_address, _) = taproot_address_from_script_leaves(
         &secp,
@@ -78,13 +90,21 @@ fn main() {
 
         // Using the challenge hashes the verifier creates their challenge transaction which has a
         // leaf script for every
-        let (challenge_address, challenge_taproot_info) =
-            generate_challenge_address_and_info(&secp, &circuit, verifier.pk, &challenge_hashes);
+        let (challenge_address, challenge_taproot_info) = generate_challenge_address_and_info(
+            &secp,
+            &verifier_circuit,
+            verifier.pk,
+            &challenge_hashes,
+        );
 
         // Create a leaf script for every gate in the circuit that is unlockable by the
         // challenge hash. This is where the gate.create_response_script methods are called
-        let (response_address, response_taproot_info) =
-            generate_response_address_and_info(&secp, &circuit, prover.pk, &challenge_hashes);
+        let (response_address, response_taproot_info) = generate_response_address_and_info(
+            &secp,
+            &verifier_circuit,
+            prover.pk,
+            &challenge_hashes,
+        );
 
         let challenge_tx = build_challenge_tx(
             &initial_fund_or_prev_response_tx.txid(),
@@ -134,12 +154,12 @@ fn main() {
         response_txs.push((response_tx, response_taproot_info));
     }
 
-    let a1 = 633;
-    let a2 = 15;
-    let b1 = number_to_bool_array(a1, 64);
-    let b2 = number_to_bool_array(a2, 64);
+    let a1 = 633u64;
+    let a2 = 15u64;
 
-    let _o = circuit.evaluate(vec![b1, b2]);
+    let _o = circuit
+        .evaluate_values::<u64>(&[&a1, &a2], Endianness::Little)
+        .unwrap_or_else(|e| panic!("Failed to evaluate circuit: {}", e));
 
     println!("Finished setup and sig exchange");
 
@@ -5,16 +5,56 @@ use std::{
 
 use bitcoin::ScriptBuf;
 
-use crate::circuit::wire::{HashValue, PreimageValue, Wire};
+use crate::circuit::{
+    gate::GateKind,
+    wire::{HashValue, PreimageOutcome, PreimageValue, Wire},
+};
 
 pub type Wires = Vec<Arc<Mutex<Wire>>>;
 
+fn wire_indexes(wires: &Wires) -> Vec<String> {
+    wires
+        .iter()
+        .map(|wire_arcm| {
+            let index = wire_arcm.lock().unwrap().index;
+            index.expect("wire index is not set").to_string()
+        })
+        .collect()
+}
+
 pub trait GateTrait {
+    fn get_gate_kind(&self) -> GateKind;
+
+    /// Name of the gate in a Bristol circuit file
+    fn get_name(&self) -> &'static str {
+        self.get_gate_kind().get_name()
+    }
+
     fn get_input_size(&self) -> usize;
     fn get_output_size(&self) -> usize;
 
-    fn get_input_wires(&mut self) -> &mut Wires;
-    fn get_output_wires(&mut self) -> &mut Wires;
+    fn get_input_wires(&self) -> &Wires;
+    fn get_output_wires(&self) -> &Wires;
+
+    /// Input operands of the gate in a Bristol circuit file. These are wire indexes, apart
+    /// from an EQ gate whose input is the constant it assigns.
+    fn get_bristol_inputs(&self) -> Vec<String> {
+        match self.get_gate_kind() {
+            GateKind::Constant(value) => vec![(value as u8).to_string()],
+            GateKind::Op(_) => wire_indexes(self.get_input_wires()),
+        }
+    }
+
+    /// The gate as a line of a Bristol circuit file
+    fn get_bristol_line(&self) -> String {
+        let inputs = self.get_bristol_inputs();
+        let outputs = wire_indexes(self.get_output_wires());
+        let mut values = vec![inputs.len().to_string(), outputs.len().to_string()];
+        values.extend(inputs);
+        values.extend(outputs);
+        values.push(self.get_name().to_string());
+        values.join(" ")
+    }
 
     fn get_input_bits(&mut self) -> Vec<bool> {
         self.get_input_wires()
@@ -24,7 +64,7 @@ pub trait GateTrait {
     }
 
     fn set_input_bits(&mut self, input_bits: Vec<bool>) {
-        for (wire_arcm, b) in zip(&mut self.get_input_wires().iter(), input_bits) {
+        for (wire_arcm, b) in zip(self.get_input_wires().iter(), input_bits) {
             wire_arcm.lock().unwrap().selector = Some(b);
         }
     }
@@ -37,7 +77,7 @@ pub trait GateTrait {
     }
 
     fn set_output_bits(&mut self, output_bits: Vec<bool>) {
-        for (wire_arcm, b) in zip(&mut self.get_output_wires().iter(), output_bits) {
+        for (wire_arcm, b) in zip(self.get_output_wires().iter(), output_bits) {
             wire_arcm.lock().unwrap().selector = Some(b);
         }
     }
@@ -67,22 +107,43 @@ pub trait GateTrait {
         witness
     }
 
-    fn add_preimages_from_witness(&mut self, witness: Vec<PreimageValue>) -> Option<Wire> {
-        let input_preimages = witness[0..self.get_input_size()].to_vec();
-        let output_preimages = witness[self.get_input_size()..].to_vec();
-        for (wire_arcm, preimage) in zip(&mut self.get_input_wires().iter(), input_preimages) {
-            let found_contradiction = wire_arcm.lock().unwrap().add_preimage(preimage);
-            if found_contradiction.is_some() {
-                return found_contradiction;
-            }
+    /// Records the preimages of a response witness, inputs then outputs, in the gate's wires.
+    /// A witness of the wrong length or holding a preimage that matches neither hash of its
+    /// wire is rejected before anything is recorded. Otherwise every preimage is recorded, and
+    /// the first wire found with both of its preimages known is returned as the contradiction.
+    fn add_preimages_from_witness(&mut self, witness: Vec<PreimageValue>) -> PreimageOutcome {
+        let wires = self
+            .get_input_wires()
+            .iter()
+            .chain(self.get_output_wires())
+            .collect::<Vec<_>>();
+        if witness.len() != wires.len()
+            || zip(&wires, &witness).any(|(wire_arcm, preimage)| {
+                wire_arcm
+                    .lock()
+                    .unwrap()
+                    .bit_of_preimage(preimage)
+                    .is_none()
+            })
+        {
+            return PreimageOutcome::InvalidPreimage;
         }
-        for (wire_arcm, preimage) in zip(&mut self.get_output_wires().iter(), output_preimages) {
-            let found_contradiction = wire_arcm.lock().unwrap().add_preimage(preimage);
-            if found_contradiction.is_some() {
-                return found_contradiction;
+
+        let mut outcome = PreimageOutcome::AlreadyKnown;
+        for (wire_arcm, preimage) in zip(wires, witness) {
+            match wire_arcm.lock().unwrap().add_preimage(preimage) {
+                PreimageOutcome::Contradiction(wire)
+                    if !matches!(outcome, PreimageOutcome::Contradiction(_)) =>
+                {
+                    outcome = PreimageOutcome::Contradiction(wire);
+                }
+                PreimageOutcome::NewBit if matches!(outcome, PreimageOutcome::AlreadyKnown) => {
+                    outcome = PreimageOutcome::NewBit;
+                }
+                _ => {}
             }
         }
-        None
+        outcome
     }
 
     fn run_gate_on_inputs(&self, inputs: Vec<bool>) -> Vec<bool>;
@@ -201,7 +201,7 @@ mod tests {
         let mut challenge_hash_manager = ChallengeHashesManager::new();
 
         let secp = Secp256k1::new();
-        let circuit = BristolCircuit::from_bristol("circuits/add.txt");
+        let circuit = BristolCircuit::from_bristol("circuits/add.txt", &[0; 32]).unwrap();
 
         let (equivocation_address, equivocation_taproot_info) =
             generate_equivocation_address_and_info(&secp, &circuit, prover.pk, verifier.pk);
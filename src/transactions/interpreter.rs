@@ -0,0 +1,937 @@
+use std::fmt;
+
+use bitcoin::{
+    absolute::LockTime,
+    hashes::{sha256, Hash},
+    key::Secp256k1,
+    opcodes::{
+        all::{
+            OP_0NOTEQUAL, OP_1ADD, OP_1SUB, OP_2DROP, OP_2DUP, OP_ABS, OP_ADD, OP_BOOLAND,
+            OP_BOOLOR, OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_CSV, OP_DEPTH, OP_DROP, OP_DUP, OP_ELSE,
+            OP_ENDIF, OP_EQUAL, OP_EQUALVERIFY, OP_FROMALTSTACK, OP_GREATERTHAN,
+            OP_GREATERTHANOREQUAL, OP_IF, OP_LESSTHAN, OP_LESSTHANOREQUAL, OP_MAX, OP_MIN,
+            OP_NEGATE, OP_NIP, OP_NOP, OP_NOT, OP_NOTIF, OP_NUMEQUAL, OP_NUMEQUALVERIFY,
+            OP_NUMNOTEQUAL, OP_OVER, OP_PICK, OP_PUSHNUM_1, OP_PUSHNUM_16, OP_PUSHNUM_NEG1,
+            OP_RETURN, OP_ROLL, OP_ROT, OP_SHA256, OP_SIZE, OP_SUB, OP_SWAP, OP_TOALTSTACK,
+            OP_TUCK, OP_VERIFY, OP_WITHIN,
+        },
+        Opcode,
+    },
+    script::Instruction,
+    secp256k1::{schnorr::Signature, Message},
+    sighash::{Prevouts, SighashCache, TapSighashType},
+    taproot::{ControlBlock, LeafVersion, TAPROOT_ANNEX_PREFIX},
+    transaction::{Sequence, Version},
+    Amount, OutPoint, Script, ScriptBuf, TapLeafHash, Transaction, TxIn, TxOut, Witness,
+    XOnlyPublicKey,
+};
+
+use crate::actor::Actor;
+
+use super::taproot_address_from_script_leaves;
+
+const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+const MAX_STACK_SIZE: usize = 1000;
+const MAX_SCRIPT_NUM_LEN: usize = 4;
+/// Timelocks can use 5 byte numbers so the full range of nSequence is reachable
+const LOCKTIME_NUM_LEN: usize = 5;
+const SEQUENCE_DISABLE_FLAG: i64 = 1 << 31;
+const SEQUENCE_TYPE_FLAG: u32 = 1 << 22;
+const SEQUENCE_MASK: u32 = SEQUENCE_TYPE_FLAG | 0xffff;
+
+/// Why a script or a script path spend was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    /// The script isn't a sequence of minimal pushes and opcodes
+    MalformedScript,
+    UnsupportedOpcode(Opcode),
+    StackUnderflow(Opcode),
+    /// A VERIFY opcode, or one of the opcodes with a VERIFY variant, found a false value
+    VerifyFailed(Opcode),
+    /// A number that is too long or not minimally encoded
+    InvalidNumber,
+    /// The argument of an IF or NOTIF is neither empty nor 0x01
+    MinimalIf,
+    UnbalancedConditional,
+    PushSize,
+    StackSize,
+    InvalidPublicKey,
+    InvalidSignature,
+    /// The script checks a signature or timelock but no spending transaction was given
+    MissingSpendContext,
+    InvalidSpendContext,
+    NegativeLocktime,
+    UnsatisfiedLocktime,
+    /// The script left a single false value on the stack
+    EvalFalse,
+    /// The script left more or less than one value on the stack
+    CleanStack {
+        size: usize,
+    },
+    WitnessTooShort,
+    UnsupportedAnnex,
+    InvalidControlBlock,
+    UnsupportedLeafVersion,
+    /// The control block and script don't commit to the output being spent
+    WrongCommitment,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::MalformedScript => write!(f, "script is malformed"),
+            ScriptError::UnsupportedOpcode(opcode) => write!(f, "unsupported opcode {}", opcode),
+            ScriptError::StackUnderflow(opcode) => {
+                write!(f, "{} needs more items than are on the stack", opcode)
+            }
+            ScriptError::VerifyFailed(opcode) => write!(f, "{} failed", opcode),
+            ScriptError::InvalidNumber => write!(f, "invalid script number"),
+            ScriptError::MinimalIf => write!(f, "IF argument must be empty or 0x01"),
+            ScriptError::UnbalancedConditional => write!(f, "unbalanced conditional"),
+            ScriptError::PushSize => write!(
+                f,
+                "stack item is larger than {} bytes",
+                MAX_SCRIPT_ELEMENT_SIZE
+            ),
+            ScriptError::StackSize => write!(f, "stack has more than {} items", MAX_STACK_SIZE),
+            ScriptError::InvalidPublicKey => write!(f, "invalid public key"),
+            ScriptError::InvalidSignature => write!(f, "invalid signature"),
+            ScriptError::MissingSpendContext => {
+                write!(f, "script needs the spending transaction")
+            }
+            ScriptError::InvalidSpendContext => {
+                write!(f, "spending transaction doesn't match its prevouts")
+            }
+            ScriptError::NegativeLocktime => write!(f, "negative locktime"),
+            ScriptError::UnsatisfiedLocktime => write!(f, "locktime requirement not satisfied"),
+            ScriptError::EvalFalse => write!(f, "script evaluated to false"),
+            ScriptError::CleanStack { size } => {
+                write!(f, "script left {} items on the stack instead of 1", size)
+            }
+            ScriptError::WitnessTooShort => write!(f, "witness is too short for a script spend"),
+            ScriptError::UnsupportedAnnex => write!(f, "witness annexes are not supported"),
+            ScriptError::InvalidControlBlock => write!(f, "invalid control block"),
+            ScriptError::UnsupportedLeafVersion => write!(f, "unsupported leaf version"),
+            ScriptError::WrongCommitment => {
+                write!(f, "control block doesn't commit to the spent output")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// The transaction input a script is executed for, needed by signature and timelock checks
+pub struct SpendContext<'a> {
+    pub tx: &'a Transaction,
+    pub input_index: usize,
+    pub prevouts: &'a [TxOut],
+    pub leaf_hash: TapLeafHash,
+}
+
+fn decode_num(item: &[u8], max_len: usize) -> Result<i64, ScriptError> {
+    if item.len() > max_len {
+        return Err(ScriptError::InvalidNumber);
+    }
+    if let Some(last) = item.last() {
+        // The last byte may only be a sign byte if the one before it needs its top bit
+        if last & 0x7f == 0 && (item.len() == 1 || item[item.len() - 2] & 0x80 == 0) {
+            return Err(ScriptError::InvalidNumber);
+        }
+    }
+    let magnitude = item
+        .iter()
+        .enumerate()
+        .fold(0i64, |acc, (i, byte)| acc | (*byte as i64) << (8 * i));
+    match item.last() {
+        Some(last) if last & 0x80 != 0 => Ok(-(magnitude & !(0x80 << (8 * (item.len() - 1))))),
+        _ => Ok(magnitude),
+    }
+}
+
+fn encode_num(value: i64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut magnitude = value.unsigned_abs();
+    while magnitude > 0 {
+        bytes.push((magnitude & 0xff) as u8);
+        magnitude >>= 8;
+    }
+    match bytes.last_mut() {
+        Some(last) if *last & 0x80 != 0 => bytes.push(if value < 0 { 0x80 } else { 0 }),
+        Some(last) if value < 0 => *last |= 0x80,
+        _ => {}
+    }
+    bytes
+}
+
+fn cast_to_bool(item: &[u8]) -> bool {
+    // Negative zero is false as well
+    item.iter()
+        .enumerate()
+        .any(|(i, byte)| *byte != 0 && !(i == item.len() - 1 && *byte == 0x80))
+}
+
+struct Interpreter<'a> {
+    stack: Vec<Vec<u8>>,
+    altstack: Vec<Vec<u8>>,
+    context: Option<&'a SpendContext<'a>>,
+}
+
+impl Interpreter<'_> {
+    fn pop(&mut self, opcode: Opcode) -> Result<Vec<u8>, ScriptError> {
+        self.stack.pop().ok_or(ScriptError::StackUnderflow(opcode))
+    }
+
+    /// Position in the stack of the item `depth` below the top
+    fn position(&self, opcode: Opcode, depth: usize) -> Result<usize, ScriptError> {
+        self.stack
+            .len()
+            .checked_sub(depth + 1)
+            .ok_or(ScriptError::StackUnderflow(opcode))
+    }
+
+    fn item(&self, opcode: Opcode, depth: usize) -> Result<Vec<u8>, ScriptError> {
+        Ok(self.stack[self.position(opcode, depth)?].clone())
+    }
+
+    fn pop_num(&mut self, opcode: Opcode) -> Result<i64, ScriptError> {
+        decode_num(&self.pop(opcode)?, MAX_SCRIPT_NUM_LEN)
+    }
+
+    fn pop_bool(&mut self, opcode: Opcode) -> Result<bool, ScriptError> {
+        Ok(cast_to_bool(&self.pop(opcode)?))
+    }
+
+    fn push_num(&mut self, value: i64) {
+        self.stack.push(encode_num(value));
+    }
+
+    fn push_bool(&mut self, value: bool) {
+        self.stack.push(if value { vec![1] } else { vec![] });
+    }
+
+    fn verify(opcode: Opcode, value: bool) -> Result<(), ScriptError> {
+        if value {
+            Ok(())
+        } else {
+            Err(ScriptError::VerifyFailed(opcode))
+        }
+    }
+
+    fn step(&mut self, opcode: Opcode) -> Result<(), ScriptError> {
+        let code = opcode.to_u8();
+        match opcode {
+            OP_PUSHNUM_NEG1 => self.push_num(-1),
+            _ if (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&code) => {
+                self.push_num((code - OP_PUSHNUM_1.to_u8() + 1).into())
+            }
+            OP_NOP => {}
+            OP_VERIFY => {
+                let value = self.pop_bool(opcode)?;
+                Self::verify(opcode, value)?
+            }
+            OP_TOALTSTACK => {
+                let item = self.pop(opcode)?;
+                self.altstack.push(item);
+            }
+            OP_FROMALTSTACK => {
+                let item = self
+                    .altstack
+                    .pop()
+                    .ok_or(ScriptError::StackUnderflow(opcode))?;
+                self.stack.push(item);
+            }
+            OP_2DROP => {
+                self.position(opcode, 1)?;
+                self.stack.truncate(self.stack.len() - 2);
+            }
+            OP_2DUP => {
+                let (a, b) = (self.item(opcode, 1)?, self.item(opcode, 0)?);
+                self.stack.extend([a, b]);
+            }
+            OP_DEPTH => self.push_num(self.stack.len() as i64),
+            OP_DROP => {
+                self.pop(opcode)?;
+            }
+            OP_DUP => self.stack.push(self.item(opcode, 0)?),
+            OP_NIP => {
+                let position = self.position(opcode, 1)?;
+                self.stack.remove(position);
+            }
+            OP_OVER => self.stack.push(self.item(opcode, 1)?),
+            OP_PICK | OP_ROLL => {
+                let depth = self.pop_num(opcode)?;
+                let depth =
+                    usize::try_from(depth).map_err(|_| ScriptError::StackUnderflow(opcode))?;
+                let position = self.position(opcode, depth)?;
+                let item = if opcode == OP_ROLL {
+                    self.stack.remove(position)
+                } else {
+                    self.stack[position].clone()
+                };
+                self.stack.push(item);
+            }
+            OP_ROT => {
+                let position = self.position(opcode, 2)?;
+                let item = self.stack.remove(position);
+                self.stack.push(item);
+            }
+            OP_SWAP => {
+                let position = self.position(opcode, 1)?;
+                let item = self.stack.remove(position);
+                self.stack.push(item);
+            }
+            OP_TUCK => {
+                let position = self.position(opcode, 1)?;
+                let item = self.item(opcode, 0)?;
+                self.stack.insert(position, item);
+            }
+            OP_SIZE => self.push_num(self.item(opcode, 0)?.len() as i64),
+            OP_EQUAL | OP_EQUALVERIFY => {
+                let equal = self.pop(opcode)? == self.pop(opcode)?;
+                if opcode == OP_EQUALVERIFY {
+                    Self::verify(opcode, equal)?
+                } else {
+                    self.push_bool(equal)
+                }
+            }
+            OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+                let a = self.pop_num(opcode)?;
+                match opcode {
+                    OP_1ADD => self.push_num(a + 1),
+                    OP_1SUB => self.push_num(a - 1),
+                    OP_NEGATE => self.push_num(-a),
+                    OP_ABS => self.push_num(a.abs()),
+                    OP_NOT => self.push_bool(a == 0),
+                    _ => self.push_bool(a != 0),
+                }
+            }
+            OP_ADD
+            | OP_SUB
+            | OP_BOOLAND
+            | OP_BOOLOR
+            | OP_NUMEQUAL
+            | OP_NUMEQUALVERIFY
+            | OP_NUMNOTEQUAL
+            | OP_LESSTHAN
+            | OP_GREATERTHAN
+            | OP_LESSTHANOREQUAL
+            | OP_GREATERTHANOREQUAL
+            | OP_MIN
+            | OP_MAX => {
+                let b = self.pop_num(opcode)?;
+                let a = self.pop_num(opcode)?;
+                match opcode {
+                    OP_ADD => self.push_num(a + b),
+                    OP_SUB => self.push_num(a - b),
+                    OP_BOOLAND => self.push_bool(a != 0 && b != 0),
+                    OP_BOOLOR => self.push_bool(a != 0 || b != 0),
+                    OP_NUMEQUAL => self.push_bool(a == b),
+                    OP_NUMEQUALVERIFY => Self::verify(opcode, a == b)?,
+                    OP_NUMNOTEQUAL => self.push_bool(a != b),
+                    OP_LESSTHAN => self.push_bool(a < b),
+                    OP_GREATERTHAN => self.push_bool(a > b),
+                    OP_LESSTHANOREQUAL => self.push_bool(a <= b),
+                    OP_GREATERTHANOREQUAL => self.push_bool(a >= b),
+                    OP_MIN => self.push_num(a.min(b)),
+                    _ => self.push_num(a.max(b)),
+                }
+            }
+            OP_WITHIN => {
+                let max = self.pop_num(opcode)?;
+                let min = self.pop_num(opcode)?;
+                let x = self.pop_num(opcode)?;
+                self.push_bool(min <= x && x < max);
+            }
+            OP_SHA256 => {
+                let item = self.pop(opcode)?;
+                self.stack
+                    .push(sha256::Hash::hash(&item).to_byte_array().to_vec());
+            }
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                let public_key = self.pop(opcode)?;
+                let signature = self.pop(opcode)?;
+                if public_key.is_empty() {
+                    return Err(ScriptError::InvalidPublicKey);
+                }
+                // An empty signature fails without aborting, and keys of unknown types are
+                // left for future soft forks so any signature passes
+                let success = !signature.is_empty();
+                if success && public_key.len() == 32 {
+                    self.check_signature(&signature, &public_key)?;
+                }
+                if opcode == OP_CHECKSIGVERIFY {
+                    Self::verify(opcode, success)?
+                } else {
+                    self.push_bool(success)
+                }
+            }
+            OP_CSV => self.check_sequence(opcode)?,
+            _ => return Err(ScriptError::UnsupportedOpcode(opcode)),
+        }
+        Ok(())
+    }
+
+    fn check_signature(&self, signature: &[u8], public_key: &[u8]) -> Result<(), ScriptError> {
+        let context = self.context.ok_or(ScriptError::MissingSpendContext)?;
+        let sighash_type = match signature.len() {
+            64 => TapSighashType::Default,
+            // The default type must be implied by a 64 byte signature
+            65 if signature[64] != 0 => TapSighashType::from_consensus_u8(signature[64])
+                .map_err(|_| ScriptError::InvalidSignature)?,
+            _ => return Err(ScriptError::InvalidSignature),
+        };
+        let sighash = SighashCache::new(context.tx)
+            .taproot_script_spend_signature_hash(
+                context.input_index,
+                &Prevouts::All(context.prevouts),
+                context.leaf_hash,
+                sighash_type,
+            )
+            .map_err(|_| ScriptError::InvalidSpendContext)?;
+        let public_key =
+            XOnlyPublicKey::from_slice(public_key).map_err(|_| ScriptError::InvalidPublicKey)?;
+        let signature =
+            Signature::from_slice(&signature[..64]).map_err(|_| ScriptError::InvalidSignature)?;
+        Secp256k1::verification_only()
+            .verify_schnorr(
+                &signature,
+                &Message::from_digest(sighash.to_byte_array()),
+                &public_key,
+            )
+            .map_err(|_| ScriptError::InvalidSignature)
+    }
+
+    /// BIP 112 relative timelock against the spending input's sequence
+    fn check_sequence(&self, opcode: Opcode) -> Result<(), ScriptError> {
+        let locktime = decode_num(&self.item(opcode, 0)?, LOCKTIME_NUM_LEN)?;
+        if locktime < 0 {
+            return Err(ScriptError::NegativeLocktime);
+        }
+        if locktime & SEQUENCE_DISABLE_FLAG != 0 {
+            return Ok(());
+        }
+        let context = self.context.ok_or(ScriptError::MissingSpendContext)?;
+        let input = context
+            .tx
+            .input
+            .get(context.input_index)
+            .ok_or(ScriptError::InvalidSpendContext)?;
+        let sequence = input.sequence.0;
+        let locktime = locktime as u32 & SEQUENCE_MASK;
+        let satisfied = context.tx.version.0 >= 2
+            && sequence & (SEQUENCE_DISABLE_FLAG as u32) == 0
+            && (locktime & SEQUENCE_TYPE_FLAG) == (sequence & SEQUENCE_TYPE_FLAG)
+            && locktime <= sequence & SEQUENCE_MASK;
+        if satisfied {
+            Ok(())
+        } else {
+            Err(ScriptError::UnsatisfiedLocktime)
+        }
+    }
+}
+
+/// Runs a tapscript on the given initial stack, bottom first as the items appear in a witness,
+/// and returns the final stack. Signature and timelock opcodes need the spending transaction in
+/// `context`. Only the opcodes the scripts in this crate use and their close relatives are
+/// supported, and the signature operation budget isn't tracked.
+pub fn execute_script(
+    script: &Script,
+    stack: Vec<Vec<u8>>,
+    context: Option<&SpendContext>,
+) -> Result<Vec<Vec<u8>>, ScriptError> {
+    if stack
+        .iter()
+        .any(|item| item.len() > MAX_SCRIPT_ELEMENT_SIZE)
+    {
+        return Err(ScriptError::PushSize);
+    }
+    let mut interpreter = Interpreter {
+        stack,
+        altstack: vec![],
+        context,
+    };
+    // Whether each enclosing IF branch is being executed
+    let mut executing: Vec<bool> = vec![];
+    for instruction in script.instructions_minimal() {
+        let instruction = instruction.map_err(|_| ScriptError::MalformedScript)?;
+        let active = executing.iter().all(|branch| *branch);
+        match instruction {
+            Instruction::PushBytes(bytes) => {
+                if bytes.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                    return Err(ScriptError::PushSize);
+                }
+                if active {
+                    interpreter.stack.push(bytes.as_bytes().to_vec());
+                }
+            }
+            Instruction::Op(opcode @ (OP_IF | OP_NOTIF)) => {
+                let mut branch = false;
+                if active {
+                    branch = match interpreter.pop(opcode)?.as_slice() {
+                        [] => false,
+                        [1] => true,
+                        _ => return Err(ScriptError::MinimalIf),
+                    };
+                    branch ^= opcode == OP_NOTIF;
+                }
+                executing.push(branch);
+            }
+            Instruction::Op(OP_ELSE) => {
+                let branch = executing
+                    .last_mut()
+                    .ok_or(ScriptError::UnbalancedConditional)?;
+                *branch = !*branch;
+            }
+            Instruction::Op(OP_ENDIF) => {
+                executing.pop().ok_or(ScriptError::UnbalancedConditional)?;
+            }
+            Instruction::Op(opcode) => {
+                if active {
+                    interpreter.step(opcode)?;
+                }
+            }
+        }
+        if interpreter.stack.len() + interpreter.altstack.len() > MAX_STACK_SIZE {
+            return Err(ScriptError::StackSize);
+        }
+    }
+    if !executing.is_empty() {
+        return Err(ScriptError::UnbalancedConditional);
+    }
+    Ok(interpreter.stack)
+}
+
+/// Validates the tapscript path spend of input `input_index` of `tx` the way a node would:
+/// the control block must commit the revealed script to the spent output, and the script must
+/// leave exactly one true value on the stack. `prevouts` are the outputs spent by every input.
+pub fn verify_script_spend(
+    tx: &Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+) -> Result<(), ScriptError> {
+    let input = tx
+        .input
+        .get(input_index)
+        .ok_or(ScriptError::InvalidSpendContext)?;
+    let prevout = prevouts
+        .get(input_index)
+        .ok_or(ScriptError::InvalidSpendContext)?;
+
+    let mut stack = input.witness.to_vec();
+    if stack.len() >= 2 && stack.last().unwrap().first() == Some(&TAPROOT_ANNEX_PREFIX) {
+        return Err(ScriptError::UnsupportedAnnex);
+    }
+    if stack.len() < 2 {
+        return Err(ScriptError::WitnessTooShort);
+    }
+    let control_block = ControlBlock::decode(&stack.pop().unwrap())
+        .map_err(|_| ScriptError::InvalidControlBlock)?;
+    let script = ScriptBuf::from_bytes(stack.pop().unwrap());
+    if control_block.leaf_version != LeafVersion::TapScript {
+        return Err(ScriptError::UnsupportedLeafVersion);
+    }
+
+    if !prevout.script_pubkey.is_p2tr() {
+        return Err(ScriptError::WrongCommitment);
+    }
+    let output_key = XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..])
+        .map_err(|_| ScriptError::WrongCommitment)?;
+    if !control_block.verify_taproot_commitment(
+        &Secp256k1::verification_only(),
+        output_key,
+        &script,
+    ) {
+        return Err(ScriptError::WrongCommitment);
+    }
+
+    let context = SpendContext {
+        tx,
+        input_index,
+        prevouts,
+        leaf_hash: TapLeafHash::from_script(&script, LeafVersion::TapScript),
+    };
+    match execute_script(&script, stack, Some(&context))?.as_slice() {
+        [item] if cast_to_bool(item) => Ok(()),
+        [_] => Err(ScriptError::EvalFalse),
+        stack => Err(ScriptError::CleanStack { size: stack.len() }),
+    }
+}
+
+/// Test harness for a single leaf: commits `script` to a fresh taproot output next to an
+/// unspendable leaf, spends it with `witness` followed by the script and control block, and
+/// validates the spend. The input carries `sequence`, and each of the `signers` signs the spend
+/// with their signature pushed before the witness in the order given, so the last signer's
+/// signature is the first one a CHECKSIG pops.
+pub fn spend_leaf(
+    script: ScriptBuf,
+    witness: Vec<Vec<u8>>,
+    signers: &[&Actor],
+    sequence: Sequence,
+) -> Result<(), ScriptError> {
+    let secp = Secp256k1::new();
+    let unspendable = ScriptBuf::from_bytes(vec![OP_RETURN.to_u8()]);
+    let (address, spend_info) =
+        taproot_address_from_script_leaves(&secp, vec![script.clone(), unspendable]);
+    let prevouts = [TxOut {
+        value: Amount::from_sat(100_000),
+        script_pubkey: address.script_pubkey(),
+    }];
+    let mut tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::new(),
+            sequence,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(99_000),
+            script_pubkey: ScriptBuf::new(),
+        }],
+    };
+
+    let sighash = SighashCache::new(&tx)
+        .taproot_script_spend_signature_hash(
+            0,
+            &Prevouts::All(&prevouts),
+            TapLeafHash::from_script(&script, LeafVersion::TapScript),
+            TapSighashType::Default,
+        )
+        .unwrap();
+    let mut items = signers
+        .iter()
+        .map(|signer| signer.sign_tx(&sighash.to_byte_array()).as_ref().to_vec())
+        .collect::<Vec<Vec<u8>>>();
+    items.extend(witness);
+    let control_block = spend_info
+        .control_block(&(script.clone(), LeafVersion::TapScript))
+        .expect("Cannot create control block");
+    items.push(script.into_bytes());
+    items.push(control_block.serialize());
+    tx.input[0].witness = Witness::from_slice(&items);
+
+    verify_script_spend(&tx, 0, &prevouts)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        opcodes::all::{
+            OP_ADD, OP_CHECKSIGVERIFY, OP_CSV, OP_ELSE, OP_ENDIF, OP_EQUALVERIFY, OP_IF,
+            OP_PUSHNUM_1, OP_ROLL,
+        },
+        script::Builder,
+        Sequence,
+    };
+
+    use crate::{
+        actor::{Actor, ActorType},
+        circuit::BristolCircuit,
+        transactions::{
+            generate_2_of_2_script, generate_anti_contradiction_script, generate_challenge_script,
+            generate_gate_response_script, generate_timelock_script,
+        },
+        utils::challenge_hashes::ChallengeHashesManager,
+    };
+
+    use super::{decode_num, encode_num, execute_script, spend_leaf, ScriptError};
+
+    #[test]
+    fn test_script_numbers() {
+        for value in [
+            0,
+            1,
+            -1,
+            127,
+            128,
+            -128,
+            255,
+            256,
+            0x7fff_ffff,
+            -0x7fff_ffff,
+        ] {
+            assert_eq!(decode_num(&encode_num(value), 4), Ok(value));
+        }
+        assert_eq!(encode_num(128), vec![0x80, 0]);
+        assert_eq!(encode_num(-1), vec![0x81]);
+        // Padded zero, negative zero and 5 byte numbers are rejected
+        assert_eq!(decode_num(&[1, 0], 4), Err(ScriptError::InvalidNumber));
+        assert_eq!(decode_num(&[0x80], 4), Err(ScriptError::InvalidNumber));
+        assert_eq!(
+            decode_num(&[1, 2, 3, 4, 5], 4),
+            Err(ScriptError::InvalidNumber)
+        );
+    }
+
+    #[test]
+    fn test_stack_and_conditionals() {
+        let script = Builder::new()
+            .push_int(3)
+            .push_int(4)
+            .push_int(5)
+            .push_int(2)
+            .push_opcode(OP_ROLL)
+            .push_opcode(OP_ADD)
+            .push_opcode(OP_IF)
+            .push_int(7)
+            .push_opcode(OP_ELSE)
+            .push_int(8)
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        // 3 is rolled on top of 5 and added, then IF pops 4 which isn't minimal
+        assert_eq!(
+            execute_script(&script, vec![], None),
+            Err(ScriptError::MinimalIf)
+        );
+
+        let script = Builder::new()
+            .push_opcode(OP_IF)
+            .push_int(7)
+            .push_opcode(OP_ELSE)
+            .push_int(8)
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        assert_eq!(
+            execute_script(&script, vec![vec![1]], None),
+            Ok(vec![vec![7]])
+        );
+        assert_eq!(
+            execute_script(&script, vec![vec![]], None),
+            Ok(vec![vec![8]])
+        );
+        assert_eq!(
+            execute_script(&script, vec![], None),
+            Err(ScriptError::StackUnderflow(OP_IF))
+        );
+
+        let unbalanced = Builder::new()
+            .push_opcode(OP_PUSHNUM_1)
+            .push_opcode(OP_IF)
+            .into_script();
+        assert_eq!(
+            execute_script(&unbalanced, vec![], None),
+            Err(ScriptError::UnbalancedConditional)
+        );
+        let verify = Builder::new().push_opcode(OP_EQUALVERIFY).into_script();
+        assert_eq!(
+            execute_script(&verify, vec![vec![1], vec![2]], None),
+            Err(ScriptError::VerifyFailed(OP_EQUALVERIFY))
+        );
+    }
+
+    #[test]
+    fn test_challenge_script() {
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+        let prover = Actor::new(ActorType::Prover, Some(0));
+        let mut manager = ChallengeHashesManager::new();
+        let (hashes, preimages) = manager.generate_challenge_hashes(2, Some(0));
+        let script = generate_challenge_script(verifier.pk, &hashes[0]);
+
+        assert_eq!(
+            spend_leaf(
+                script.clone(),
+                vec![preimages[0].to_vec()],
+                &[&verifier],
+                Sequence::ENABLE_RBF_NO_LOCKTIME
+            ),
+            Ok(())
+        );
+        // The preimage of another gate's challenge
+        assert_eq!(
+            spend_leaf(
+                script.clone(),
+                vec![preimages[1].to_vec()],
+                &[&verifier],
+                Sequence::ENABLE_RBF_NO_LOCKTIME
+            ),
+            Err(ScriptError::VerifyFailed(OP_EQUALVERIFY))
+        );
+        assert_eq!(
+            spend_leaf(
+                script.clone(),
+                vec![preimages[0].to_vec()],
+                &[&prover],
+                Sequence::ENABLE_RBF_NO_LOCKTIME
+            ),
+            Err(ScriptError::InvalidSignature)
+        );
+        assert_eq!(
+            spend_leaf(
+                script,
+                vec![vec![], preimages[0].to_vec()],
+                &[],
+                Sequence::ENABLE_RBF_NO_LOCKTIME
+            ),
+            Err(ScriptError::EvalFalse)
+        );
+    }
+
+    #[test]
+    fn test_anti_contradiction_script() {
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+        let circuit = BristolCircuit::from_bristol("circuits/add.txt", &[0; 32]).unwrap();
+        let wire = circuit.wires[0].lock().unwrap().clone();
+        let preimages = wire.preimages.unwrap();
+        let (zero, one) = (
+            preimages.zero.unwrap().to_vec(),
+            preimages.one.unwrap().to_vec(),
+        );
+        let script = generate_anti_contradiction_script(wire.get_hash_pair(), verifier.pk);
+
+        // The zero preimage is checked first so it sits on top
+        assert_eq!(
+            spend_leaf(
+                script.clone(),
+                vec![one.clone(), zero.clone()],
+                &[&verifier],
+                Sequence::ENABLE_RBF_NO_LOCKTIME
+            ),
+            Ok(())
+        );
+        for witness in [
+            vec![zero.clone(), one.clone()],
+            vec![zero.clone(), zero.clone()],
+            vec![one.clone(), one.clone()],
+        ] {
+            assert_eq!(
+                spend_leaf(
+                    script.clone(),
+                    witness,
+                    &[&verifier],
+                    Sequence::ENABLE_RBF_NO_LOCKTIME
+                ),
+                Err(ScriptError::VerifyFailed(OP_EQUALVERIFY))
+            );
+        }
+        // Another wire's preimages don't slash this one
+        let other = circuit.wires[1].lock().unwrap().preimages.unwrap();
+        assert_eq!(
+            spend_leaf(
+                script,
+                vec![other.one.unwrap().to_vec(), other.zero.unwrap().to_vec()],
+                &[&verifier],
+                Sequence::ENABLE_RBF_NO_LOCKTIME
+            ),
+            Err(ScriptError::VerifyFailed(OP_EQUALVERIFY))
+        );
+    }
+
+    #[test]
+    fn test_gate_response_leaf() {
+        let prover = Actor::new(ActorType::Prover, Some(0));
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &[0; 32]).unwrap();
+        circuit.evaluate(vec![vec![true; 64], vec![false; 64]]);
+        let mut manager = ChallengeHashesManager::new();
+        let (hashes, preimages) = manager.generate_challenge_hashes(circuit.gates.len(), Some(0));
+
+        for i in [0, 1, circuit.gates.len() - 1] {
+            let script = generate_gate_response_script(&circuit.gates[i], &hashes[i], prover.pk);
+            let witness = circuit.gates[i]
+                .create_response_witness(preimages[i])
+                .iter()
+                .map(|item| item.to_vec())
+                .collect::<Vec<Vec<u8>>>();
+            assert_eq!(
+                spend_leaf(
+                    script.clone(),
+                    witness.clone(),
+                    &[&prover],
+                    Sequence::ENABLE_RBF_NO_LOCKTIME
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                spend_leaf(
+                    script,
+                    witness,
+                    &[&verifier],
+                    Sequence::ENABLE_RBF_NO_LOCKTIME
+                ),
+                Err(ScriptError::InvalidSignature)
+            );
+        }
+    }
+
+    #[test]
+    fn test_timelock_and_multisig_scripts() {
+        let prover = Actor::new(ActorType::Prover, Some(0));
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+
+        // The timelock leaf spends once the input's relative locktime covers the blocks
+        let timelock = generate_timelock_script(prover.pk, 10);
+        for blocks in [10, 11] {
+            assert_eq!(
+                spend_leaf(
+                    timelock.clone(),
+                    vec![],
+                    &[&prover],
+                    Sequence::from_height(blocks)
+                ),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            spend_leaf(
+                timelock.clone(),
+                vec![],
+                &[&prover],
+                Sequence::from_height(9)
+            ),
+            Err(ScriptError::UnsatisfiedLocktime)
+        );
+        assert_eq!(
+            spend_leaf(
+                timelock.clone(),
+                vec![],
+                &[&prover],
+                Sequence::ENABLE_RBF_NO_LOCKTIME
+            ),
+            Err(ScriptError::UnsatisfiedLocktime)
+        );
+        assert_eq!(
+            spend_leaf(timelock, vec![], &[&verifier], Sequence::from_height(10)),
+            Err(ScriptError::InvalidSignature)
+        );
+        let disabled = Builder::new()
+            .push_int(1 << 31 | 10)
+            .push_opcode(OP_CSV)
+            .into_script();
+        assert_eq!(
+            execute_script(&disabled, vec![], None).map(|stack| stack.len()),
+            Ok(1)
+        );
+
+        // The 2-of-2 leaf checks the prover's signature first, so it goes on top
+        let multisig = generate_2_of_2_script(prover.pk, verifier.pk);
+        assert_eq!(
+            spend_leaf(
+                multisig.clone(),
+                vec![],
+                &[&verifier, &prover],
+                Sequence::ENABLE_RBF_NO_LOCKTIME
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            spend_leaf(
+                multisig.clone(),
+                vec![],
+                &[&prover, &verifier],
+                Sequence::ENABLE_RBF_NO_LOCKTIME
+            ),
+            Err(ScriptError::InvalidSignature)
+        );
+        assert_eq!(
+            spend_leaf(
+                multisig,
+                vec![vec![]],
+                &[&verifier],
+                Sequence::ENABLE_RBF_NO_LOCKTIME
+            ),
+            Err(ScriptError::VerifyFailed(OP_CHECKSIGVERIFY))
+        );
+    }
+}
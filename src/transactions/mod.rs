@@ -1,4 +1,6 @@
 pub mod challenge;
+#[cfg(test)]
+pub mod interpreter;
 pub mod witness;
 
 use std::str::FromStr;
@@ -6,8 +8,8 @@ use std::str::FromStr;
 use bitcoin::{
     key::Secp256k1,
     opcodes::all::{
-        OP_BOOLOR, OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_CSV, OP_DUP, OP_EQUAL, OP_EQUALVERIFY,
-        OP_ROT, OP_SHA256, OP_VERIFY,
+        OP_BOOLOR, OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_CSV, OP_DROP, OP_DUP, OP_EQUAL,
+        OP_EQUALVERIFY, OP_ROT, OP_SHA256, OP_VERIFY,
     },
     script::Builder,
     secp256k1::All,
@@ -18,6 +20,7 @@ use bitcoin::{
 use crate::{
     circuit::{
         wire::{HashTuple, HashValue},
+        word_gate::WordGate,
         BristolCircuit,
     },
     traits::gate::GateTrait,
@@ -81,6 +84,8 @@ pub fn generate_timelock_script(actor_pk: XOnlyPublicKey, block_count: u32) -> S
     Builder::new()
         .push_int(block_count as i64)
         .push_opcode(OP_CSV)
+        // CSV leaves the block count on the stack
+        .push_opcode(OP_DROP)
         .push_x_only_key(&actor_pk)
         .push_opcode(OP_CHECKSIG)
         .into_script()
@@ -95,26 +100,33 @@ pub fn generate_2_of_2_script(prover_pk: XOnlyPublicKey, verifier_pk: XOnlyPubli
         .into_script()
 }
 
+/// Depth of the deepest leaf in the tree built by `taproot_address_from_script_leaves`
+pub fn taproot_tree_depth(num_leaves: usize) -> u8 {
+    assert!(num_leaves > 1, "more than one script is required");
+    ((num_leaves - 1).ilog2() + 1) as u8 // ceil(log(n))
+}
+
+// An unspendable public key, which prevents the Public Key side of the taproot from being
+// spent
+// https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki#constructing-and-spending-taproot-outputs
+pub fn unspendable_internal_key() -> XOnlyPublicKey {
+    XOnlyPublicKey::from_str("93c7378d96518a75448821c4f7c8f4bae7ce60f804d03d1f0628dd5dd0f5de51")
+        .unwrap()
+}
+
 pub fn taproot_address_from_script_leaves(
     secp: &Secp256k1<All>,
     scripts: Vec<ScriptBuf>,
 ) -> (Address, TaprootSpendInfo) {
     let n = scripts.len();
-    assert!(n > 1, "more than one script is required");
-    let m: u8 = ((n - 1).ilog2() + 1) as u8; // m = ceil(log(n))
+    let m = taproot_tree_depth(n);
     let k = 2_usize.pow(m.into()) - n;
     let taproot = (0..n).fold(TaprootBuilder::new(), |acc, i| {
         acc.add_leaf(m - ((i >= n - k) as u8), scripts[i].clone())
             .unwrap()
     });
 
-    // An unspendable public key, which prevents the Public Key side of the taproot from being
-    // spent
-    // https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki#constructing-and-spending-taproot-outputs
-    let internal_key = XOnlyPublicKey::from_str(
-        "93c7378d96518a75448821c4f7c8f4bae7ce60f804d03d1f0628dd5dd0f5de51",
-    )
-    .unwrap();
+    let internal_key = unspendable_internal_key();
     let tree_info = taproot.finalize(secp, internal_key).unwrap();
     let address = Address::p2tr(
         secp,
@@ -181,12 +193,42 @@ pub fn generate_gate_response_script(
     challenge_hash: &HashValue,
     prover_pk: XOnlyPublicKey,
 ) -> ScriptBuf {
-    Builder::from(
-        gate.create_response_script(*challenge_hash)
-            .as_bytes()
-            .to_vec(),
-    )
-    .push_x_only_key(&prover_pk)
-    .push_opcode(OP_CHECKSIG)
-    .into_script()
+    add_prover_signature_check(gate.create_response_script(*challenge_hash), prover_pk)
+}
+
+/// Response leaves of word gates, which commit to whole words rather than single bits
+pub fn generate_word_response_address_and_info(
+    secp: &Secp256k1<All>,
+    gates: &[WordGate],
+    prover_pk: XOnlyPublicKey,
+    challenge_hashes: &[HashValue],
+) -> (Address, TaprootSpendInfo) {
+    assert_eq!(
+        challenge_hashes.len(),
+        gates.len(),
+        "wrong number of challenge hashes"
+    );
+    let scripts = gates
+        .iter()
+        .zip(challenge_hashes.iter())
+        .map(|(gate, hash)| generate_word_gate_response_script(gate, hash, prover_pk))
+        .collect::<Vec<ScriptBuf>>();
+    taproot_address_from_script_leaves(secp, scripts)
+}
+
+pub fn generate_word_gate_response_script(
+    gate: &WordGate,
+    challenge_hash: &HashValue,
+    prover_pk: XOnlyPublicKey,
+) -> ScriptBuf {
+    add_prover_signature_check(gate.create_response_script(*challenge_hash), prover_pk)
+}
+
+/// Ends a gate's response script with the prover's signature check, so only the prover can
+/// answer a challenge
+fn add_prover_signature_check(response_script: ScriptBuf, prover_pk: XOnlyPublicKey) -> ScriptBuf {
+    Builder::from(response_script.into_bytes())
+        .push_x_only_key(&prover_pk)
+        .push_opcode(OP_CHECKSIG)
+        .into_script()
 }
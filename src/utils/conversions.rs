@@ -1,7 +1,10 @@
+use std::fmt;
+
 pub fn number_to_bool_array(number: usize, length: usize) -> Vec<bool> {
     let mut v = Vec::new();
     for i in 0..length {
-        v.push(0 != number & (1 << i));
+        // Bits past the width of usize are always 0
+        v.push(i < usize::BITS as usize && 0 != number & (1 << i));
     }
     v
 }
@@ -16,3 +19,297 @@ pub fn bool_array_to_number(bool_array: Vec<bool>) -> usize {
     }
     a
 }
+
+/// Order of the bits of a value on the circuit's wires. With `Little` the first wire holds the
+/// least significant bit, with `Big` it holds the most significant one. Byte arrays are read as
+/// an integer of the same endianness, so `Big` keeps the bytes in order with each byte written
+/// most significant bit first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    /// The value doesn't fit in the wires it is assigned to
+    ValueTooLarge {
+        size: usize,
+    },
+    /// The wires hold more bits than the type can represent
+    TypeTooSmall {
+        size: usize,
+        capacity: usize,
+    },
+    /// A byte array doesn't have exactly the number of bytes of its wires
+    ByteLengthMismatch {
+        size: usize,
+        bytes: usize,
+    },
+    WrongNumberOfValues {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::ValueTooLarge { size } => {
+                write!(f, "value does not fit in {} bits", size)
+            }
+            ConversionError::TypeTooSmall { size, capacity } => {
+                write!(f, "{} bits do not fit in a type of {} bits", size, capacity)
+            }
+            ConversionError::ByteLengthMismatch { size, bytes } => {
+                write!(f, "{} bytes can't be assigned to {} bits", bytes, size)
+            }
+            ConversionError::WrongNumberOfValues { expected, found } => {
+                write!(f, "expected {} values, found {}", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// A value that can be assigned to, or read from, a group of circuit wires
+pub trait CircuitValue {
+    fn to_bits(&self, size: usize, endianness: Endianness) -> Result<Vec<bool>, ConversionError>;
+
+    fn from_bits(bits: &[bool], endianness: Endianness) -> Result<Self, ConversionError>
+    where
+        Self: Sized;
+}
+
+macro_rules! impl_circuit_value {
+    ($($t:ty),*) => {
+        $(
+            impl CircuitValue for $t {
+                fn to_bits(
+                    &self,
+                    size: usize,
+                    endianness: Endianness,
+                ) -> Result<Vec<bool>, ConversionError> {
+                    if size < <$t>::BITS as usize && *self >> size != 0 {
+                        return Err(ConversionError::ValueTooLarge { size });
+                    }
+                    let mut bits = (0..size)
+                        .map(|i| i < <$t>::BITS as usize && (*self >> i) & 1 == 1)
+                        .collect::<Vec<bool>>();
+                    if endianness == Endianness::Big {
+                        bits.reverse();
+                    }
+                    Ok(bits)
+                }
+
+                fn from_bits(
+                    bits: &[bool],
+                    endianness: Endianness,
+                ) -> Result<Self, ConversionError> {
+                    if bits.len() > <$t>::BITS as usize {
+                        return Err(ConversionError::TypeTooSmall {
+                            size: bits.len(),
+                            capacity: <$t>::BITS as usize,
+                        });
+                    }
+                    let value = |acc: $t, bit: &bool| (acc << 1) | *bit as $t;
+                    Ok(match endianness {
+                        Endianness::Little => bits.iter().rev().fold(0, value),
+                        Endianness::Big => bits.iter().fold(0, value),
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_circuit_value!(u8, u16, u32, u64, u128);
+
+fn bytes_to_bits(
+    bytes: &[u8],
+    size: usize,
+    endianness: Endianness,
+) -> Result<Vec<bool>, ConversionError> {
+    if size != bytes.len() * 8 {
+        return Err(ConversionError::ByteLengthMismatch {
+            size,
+            bytes: bytes.len(),
+        });
+    }
+    let mut bits = Vec::with_capacity(size);
+    for byte in bytes {
+        bits.extend(byte.to_bits(8, endianness)?);
+    }
+    Ok(bits)
+}
+
+impl CircuitValue for Vec<u8> {
+    fn to_bits(&self, size: usize, endianness: Endianness) -> Result<Vec<bool>, ConversionError> {
+        bytes_to_bits(self, size, endianness)
+    }
+
+    fn from_bits(bits: &[bool], endianness: Endianness) -> Result<Self, ConversionError> {
+        if !bits.len().is_multiple_of(8) {
+            return Err(ConversionError::ByteLengthMismatch {
+                size: bits.len(),
+                bytes: bits.len().div_ceil(8),
+            });
+        }
+        bits.chunks(8)
+            .map(|byte| u8::from_bits(byte, endianness))
+            .collect()
+    }
+}
+
+impl<const N: usize> CircuitValue for [u8; N] {
+    fn to_bits(&self, size: usize, endianness: Endianness) -> Result<Vec<bool>, ConversionError> {
+        bytes_to_bits(self, size, endianness)
+    }
+
+    fn from_bits(bits: &[bool], endianness: Endianness) -> Result<Self, ConversionError> {
+        let bytes = Vec::<u8>::from_bits(bits, endianness)?;
+        bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| ConversionError::ByteLengthMismatch {
+                size: N * 8,
+                bytes: bytes.len(),
+            })
+    }
+}
+
+/// Converts one value per declared input into the wire bits `evaluate` takes
+pub fn values_to_inputs(
+    values: &[&dyn CircuitValue],
+    input_wire_sizes: &[usize],
+    endianness: Endianness,
+) -> Result<Vec<Vec<bool>>, ConversionError> {
+    if values.len() != input_wire_sizes.len() {
+        return Err(ConversionError::WrongNumberOfValues {
+            expected: input_wire_sizes.len(),
+            found: values.len(),
+        });
+    }
+    values
+        .iter()
+        .zip(input_wire_sizes)
+        .map(|(value, size)| value.to_bits(*size, endianness))
+        .collect()
+}
+
+/// Reads every output returned by `evaluate` as a `T`
+pub fn outputs_to_values<T: CircuitValue>(
+    outputs: &[Vec<bool>],
+    endianness: Endianness,
+) -> Result<Vec<T>, ConversionError> {
+    outputs
+        .iter()
+        .map(|bits| T::from_bits(bits, endianness))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        number_to_bool_array, outputs_to_values, values_to_inputs, CircuitValue, ConversionError,
+        Endianness,
+    };
+
+    #[test]
+    fn test_number_to_bool_array_past_64_bits() {
+        let bits = number_to_bool_array(5, 128);
+        assert_eq!(bits.len(), 128);
+        assert_eq!(bits[..3], [true, false, true]);
+        assert!(bits[3..].iter().all(|b| !b));
+    }
+
+    #[test]
+    fn test_integer_round_trip() {
+        for endianness in [Endianness::Little, Endianness::Big] {
+            let value = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210u128;
+            let bits = value.to_bits(128, endianness).unwrap();
+            assert_eq!(u128::from_bits(&bits, endianness).unwrap(), value);
+
+            let bits = 0xa5u8.to_bits(12, endianness).unwrap();
+            assert_eq!(bits.len(), 12);
+            assert_eq!(u32::from_bits(&bits, endianness).unwrap(), 0xa5);
+        }
+    }
+
+    #[test]
+    fn test_bit_order() {
+        assert_eq!(
+            6u8.to_bits(4, Endianness::Little).unwrap(),
+            vec![false, true, true, false]
+        );
+        assert_eq!(
+            6u8.to_bits(4, Endianness::Big).unwrap(),
+            vec![false, true, true, false]
+                .into_iter()
+                .rev()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            6u8.to_bits(4, Endianness::Little).unwrap(),
+            number_to_bool_array(6, 4)
+        );
+    }
+
+    #[test]
+    fn test_byte_arrays() {
+        let bytes = [0x80u8, 0x01];
+        let bits = bytes.to_bits(16, Endianness::Big).unwrap();
+        assert!(bits[0] && bits[15]);
+        assert_eq!(bits.iter().filter(|b| **b).count(), 2);
+        assert_eq!(<[u8; 2]>::from_bits(&bits, Endianness::Big).unwrap(), bytes);
+        assert_eq!(
+            Vec::<u8>::from_bits(&bits, Endianness::Big).unwrap(),
+            bytes.to_vec()
+        );
+
+        // Little endian byte arrays are the same bits as the little endian integer
+        assert_eq!(
+            bytes.to_bits(16, Endianness::Little).unwrap(),
+            u16::from_le_bytes(bytes)
+                .to_bits(16, Endianness::Little)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        assert_eq!(
+            300u32.to_bits(8, Endianness::Little),
+            Err(ConversionError::ValueTooLarge { size: 8 })
+        );
+        assert_eq!(
+            u8::from_bits(&[false; 9], Endianness::Little),
+            Err(ConversionError::TypeTooSmall {
+                size: 9,
+                capacity: 8
+            })
+        );
+        assert_eq!(
+            [0u8; 3].to_bits(16, Endianness::Big),
+            Err(ConversionError::ByteLengthMismatch { size: 16, bytes: 3 })
+        );
+        assert_eq!(
+            values_to_inputs(&[&1u8], &[8, 8], Endianness::Little),
+            Err(ConversionError::WrongNumberOfValues {
+                expected: 2,
+                found: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_inputs_and_outputs() {
+        let inputs = values_to_inputs(&[&7u64, &[1u8, 2]], &[64, 16], Endianness::Little).unwrap();
+        assert_eq!(inputs[0].len(), 64);
+        assert_eq!(inputs[1].len(), 16);
+        assert_eq!(
+            outputs_to_values::<u64>(&inputs, Endianness::Little).unwrap(),
+            vec![7, 0x0201]
+        );
+    }
+}
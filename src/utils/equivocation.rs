@@ -0,0 +1,497 @@
+use std::fmt;
+
+use bitcoin::{taproot::TaprootSpendInfo, Transaction, Txid, XOnlyPublicKey};
+use bitcoincore_rpc::RpcApi;
+
+use crate::{
+    actor::Actor,
+    circuit::{wire::HashValue, BristolCircuit},
+    transactions::challenge::build_equivocation_response_tx,
+};
+
+use super::witness::{
+    add_preimages_from_gate_response, fill_response_tx_with_witness_for_equivocation,
+    ResponseWitnessError,
+};
+
+#[derive(Debug)]
+pub enum EquivocationError {
+    Response(ResponseWitnessError),
+    Rpc(bitcoincore_rpc::Error),
+}
+
+impl fmt::Display for EquivocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EquivocationError::Response(e) => write!(f, "failed to read response: {}", e),
+            EquivocationError::Rpc(e) => write!(f, "failed to broadcast slashing tx: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EquivocationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EquivocationError::Response(e) => Some(e),
+            EquivocationError::Rpc(e) => Some(e),
+        }
+    }
+}
+
+impl From<ResponseWitnessError> for EquivocationError {
+    fn from(e: ResponseWitnessError) -> Self {
+        EquivocationError::Response(e)
+    }
+}
+
+impl From<bitcoincore_rpc::Error> for EquivocationError {
+    fn from(e: bitcoincore_rpc::Error) -> Self {
+        EquivocationError::Rpc(e)
+    }
+}
+
+/// The verifier's watch over the prover's responses. Every revealed preimage is kept in the
+/// wires of the verifier's circuit, which are shared by all the gates reading or writing them,
+/// so a wire revealed with both values is caught whichever rounds and gates revealed them.
+pub struct EquivocationDetector {
+    pub circuit: BristolCircuit,
+    prover_pk: XOnlyPublicKey,
+    equivocation_taproot_info: TaprootSpendInfo,
+    amount: u64,
+    fee: u64,
+    dust_limit: u64,
+}
+
+impl EquivocationDetector {
+    /// `circuit` is the verifier's copy, built from the prover's commitment. The amounts are the
+    /// ones the challenge and response transactions were built with.
+    pub fn new(
+        circuit: BristolCircuit,
+        prover_pk: XOnlyPublicKey,
+        equivocation_taproot_info: TaprootSpendInfo,
+        amount: u64,
+        fee: u64,
+        dust_limit: u64,
+    ) -> Self {
+        EquivocationDetector {
+            circuit,
+            prover_pk,
+            equivocation_taproot_info,
+            amount,
+            fee,
+            dust_limit,
+        }
+    }
+
+    /// Records the preimages revealed by `challenge_tx`, the round `round` challenge that spends
+    /// `response_tx`'s response output. If a wire now has both preimages, returns the slashing
+    /// transaction taking `challenge_tx`'s equivocation output, signed by the verifier.
+    pub fn process_response(
+        &mut self,
+        verifier: &Actor,
+        challenge_tx: &Transaction,
+        response_tx: &Transaction,
+        challenge_hashes: &[HashValue],
+        round: u64,
+    ) -> Result<Option<Transaction>, ResponseWitnessError> {
+        let (_, contradiction) = add_preimages_from_gate_response(
+            challenge_tx,
+            response_tx,
+            &mut self.circuit,
+            challenge_hashes,
+            self.prover_pk,
+        )?;
+        let wire = match contradiction {
+            Some(wire) => wire,
+            None => return Ok(None),
+        };
+
+        let mut slashing_tx = build_equivocation_response_tx(
+            challenge_tx,
+            &verifier.address,
+            self.amount,
+            self.fee,
+            self.dust_limit,
+            round,
+        );
+        fill_response_tx_with_witness_for_equivocation(
+            &mut slashing_tx,
+            challenge_tx,
+            verifier,
+            &self.equivocation_taproot_info,
+            wire.get_hash_pair(),
+            wire.preimages.unwrap(),
+        );
+        Ok(Some(slashing_tx))
+    }
+
+    /// Same as `process_response`, broadcasting the slashing transaction as soon as the prover
+    /// is caught
+    pub fn process_response_and_slash(
+        &mut self,
+        rpc: &impl RpcApi,
+        verifier: &Actor,
+        challenge_tx: &Transaction,
+        response_tx: &Transaction,
+        challenge_hashes: &[HashValue],
+        round: u64,
+    ) -> Result<Option<Txid>, EquivocationError> {
+        match self.process_response(verifier, challenge_tx, response_tx, challenge_hashes, round)? {
+            Some(slashing_tx) => Ok(Some(rpc.send_raw_transaction(&slashing_tx)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use bitcoin::{
+        consensus::deserialize,
+        hashes::{hex::FromHex, Hash},
+        key::Secp256k1,
+        taproot::TaprootSpendInfo,
+        Transaction, Txid,
+    };
+    use bitcoincore_rpc::RpcApi;
+
+    use crate::{
+        actor::{Actor, ActorType},
+        circuit::{wire::HashValue, BristolCircuit},
+        transactions::{
+            challenge::{build_challenge_tx, build_response_tx},
+            generate_2_of_2_script, generate_challenge_address_and_info,
+            generate_equivocation_address_and_info, generate_response_address_and_info,
+            generate_timelock_script,
+            interpreter::verify_script_spend,
+            taproot_address_from_script_leaves,
+        },
+        utils::{
+            challenge_hashes::ChallengeHashesManager,
+            witness::fill_challenge_tx_with_witness_for_gate_response,
+        },
+    };
+
+    use super::EquivocationDetector;
+
+    const AMOUNT: u64 = 100_000;
+    const FEE: u64 = 500;
+    const DUST_LIMIT: u64 = 546;
+
+    /// Records the transactions it is asked to broadcast
+    #[derive(Default)]
+    struct MockRpc {
+        sent: RefCell<Vec<Transaction>>,
+    }
+
+    impl RpcApi for MockRpc {
+        fn call<T: for<'a> serde::de::Deserialize<'a>>(
+            &self,
+            cmd: &str,
+            args: &[serde_json::Value],
+        ) -> bitcoincore_rpc::Result<T> {
+            assert_eq!(cmd, "sendrawtransaction");
+            let bytes = Vec::<u8>::from_hex(args[0].as_str().unwrap()).unwrap();
+            let tx: Transaction = deserialize(&bytes).unwrap();
+            let txid = tx.txid();
+            self.sent.borrow_mut().push(tx);
+            Ok(serde_json::from_value(serde_json::to_value(txid)?)?)
+        }
+    }
+
+    /// A round of the protocol: the response transaction and the next challenge transaction
+    /// that spends it, which carries the prover's answer
+    struct Round {
+        challenge_hashes: Vec<HashValue>,
+        response_tx: Transaction,
+        response_taproot_info: TaprootSpendInfo,
+        next_challenge_tx: Transaction,
+    }
+
+    /// Builds `rounds` linked rounds without touching a node, with the equivocation taproot
+    /// info shared by all of them
+    fn build_rounds(
+        circuit: &BristolCircuit,
+        prover: &Actor,
+        verifier: &Actor,
+        challenge_hash_manager: &mut ChallengeHashesManager,
+        rounds: u64,
+    ) -> (Vec<Round>, TaprootSpendInfo) {
+        let secp = Secp256k1::new();
+        let (equivocation_address, equivocation_taproot_info) =
+            generate_equivocation_address_and_info(&secp, circuit, prover.pk, verifier.pk);
+        let (response_second_address, _) = taproot_address_from_script_leaves(
+            &secp,
+            vec![
+                generate_timelock_script(verifier.pk, 10),
+                generate_2_of_2_script(prover.pk, verifier.pk),
+            ],
+        );
+
+        let mut all_hashes = vec![];
+        for round in 0..=rounds {
+            let (challenge_hashes, _) =
+                challenge_hash_manager.generate_challenge_hashes(circuit.gates.len(), Some(round));
+            all_hashes.push(challenge_hashes);
+        }
+
+        let mut result = vec![];
+        let mut pending_challenge_tx: Option<Transaction> = None;
+        for round in 0..rounds {
+            let challenge_hashes = &all_hashes[round as usize];
+            let challenge_tx = pending_challenge_tx.take().unwrap_or_else(|| {
+                let (challenge_address, _) = generate_challenge_address_and_info(
+                    &secp,
+                    circuit,
+                    verifier.pk,
+                    challenge_hashes,
+                );
+                build_challenge_tx(
+                    &Txid::all_zeros(),
+                    &challenge_address,
+                    &equivocation_address,
+                    AMOUNT,
+                    FEE,
+                    DUST_LIMIT,
+                    round,
+                    0,
+                )
+            });
+            let (response_address, response_taproot_info) =
+                generate_response_address_and_info(&secp, circuit, prover.pk, challenge_hashes);
+            let response_tx = build_response_tx(
+                &challenge_tx,
+                &response_address,
+                &response_second_address,
+                AMOUNT,
+                FEE,
+                DUST_LIMIT,
+                round,
+            );
+            let (next_challenge_address, _) = generate_challenge_address_and_info(
+                &secp,
+                circuit,
+                verifier.pk,
+                &all_hashes[round as usize + 1],
+            );
+            let next_challenge_tx = build_challenge_tx(
+                &response_tx.txid(),
+                &next_challenge_address,
+                &equivocation_address,
+                AMOUNT,
+                FEE,
+                DUST_LIMIT,
+                round + 1,
+                0,
+            );
+            result.push(Round {
+                challenge_hashes: challenge_hashes.clone(),
+                response_tx,
+                response_taproot_info,
+                next_challenge_tx: next_challenge_tx.clone(),
+            });
+            pending_challenge_tx = Some(next_challenge_tx);
+        }
+        (result, equivocation_taproot_info)
+    }
+
+    /// The prover answers the challenge of `round` for `gate_num`
+    fn respond(
+        round: &mut Round,
+        round_num: u64,
+        circuit: &mut BristolCircuit,
+        prover: &Actor,
+        challenge_hash_manager: &ChallengeHashesManager,
+        gate_num: usize,
+    ) {
+        fill_challenge_tx_with_witness_for_gate_response(
+            &mut round.next_challenge_tx,
+            &round.response_tx,
+            prover,
+            &mut circuit.gates[gate_num],
+            challenge_hash_manager.get_challenge_preimage(round_num as usize, gate_num),
+            &round.response_taproot_info,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_slashes_equivocation_across_rounds_and_gates() {
+        let prover = Actor::new(ActorType::Prover, Some(0));
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &[0; 32]).unwrap();
+        let verifier_circuit =
+            BristolCircuit::from_bristol("circuits/add.txt", &circuit.commitment()).unwrap();
+        let mut challenge_hash_manager = ChallengeHashesManager::new();
+        let (mut rounds, equivocation_taproot_info) =
+            build_rounds(&circuit, &prover, &verifier, &mut challenge_hash_manager, 3);
+        let mut detector = EquivocationDetector::new(
+            verifier_circuit,
+            prover.pk,
+            equivocation_taproot_info,
+            AMOUNT,
+            FEE,
+            DUST_LIMIT,
+        );
+        let rpc = MockRpc::default();
+
+        // Two gates reading the lowest bit of the first input
+        let readers: Vec<usize> = (0..circuit.gates.len())
+            .filter(|&gate_num| {
+                circuit.gates[gate_num]
+                    .get_input_wires()
+                    .iter()
+                    .any(|wire| wire.lock().unwrap().index == Some(0))
+            })
+            .collect();
+        assert!(readers.len() > 1);
+
+        // Consistent answers to different gates don't slash anyone
+        circuit.evaluate(vec![vec![true; 64], vec![false; 64]]);
+        for (round_num, gate_num) in [(0, readers[0]), (1, circuit.gates.len() - 1)] {
+            let round = &mut rounds[round_num as usize];
+            respond(
+                round,
+                round_num,
+                &mut circuit,
+                &prover,
+                &challenge_hash_manager,
+                gate_num,
+            );
+            let slashed = detector
+                .process_response_and_slash(
+                    &rpc,
+                    &verifier,
+                    &round.next_challenge_tx,
+                    &round.response_tx,
+                    &round.challenge_hashes,
+                    round_num + 1,
+                )
+                .unwrap();
+            assert_eq!(slashed, None);
+        }
+        assert!(rpc.sent.borrow().is_empty());
+
+        // Two rounds later the prover answers another gate from a different evaluation,
+        // revealing the other bit of the shared wire
+        circuit.evaluate(vec![vec![false; 64], vec![false; 64]]);
+        let round = &mut rounds[2];
+        respond(
+            round,
+            2,
+            &mut circuit,
+            &prover,
+            &challenge_hash_manager,
+            readers[1],
+        );
+        let slashed = detector
+            .process_response_and_slash(
+                &rpc,
+                &verifier,
+                &round.next_challenge_tx,
+                &round.response_tx,
+                &round.challenge_hashes,
+                3,
+            )
+            .unwrap();
+
+        let sent = rpc.sent.borrow();
+        assert_eq!(sent.len(), 1);
+        let slashing_tx = &sent[0];
+        assert_eq!(slashed, Some(slashing_tx.txid()));
+        assert_eq!(
+            slashing_tx.input[0].previous_output.txid,
+            round.next_challenge_tx.txid()
+        );
+        assert_eq!(
+            verify_script_spend(slashing_tx, 0, &[round.next_challenge_tx.output[1].clone()]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_slashes_equivocation_on_gate_output() {
+        let prover = Actor::new(ActorType::Prover, Some(0));
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &[0; 32]).unwrap();
+        let verifier_circuit =
+            BristolCircuit::from_bristol("circuits/add.txt", &circuit.commitment()).unwrap();
+        let mut challenge_hash_manager = ChallengeHashesManager::new();
+        let (mut rounds, equivocation_taproot_info) =
+            build_rounds(&circuit, &prover, &verifier, &mut challenge_hash_manager, 2);
+        let mut detector = EquivocationDetector::new(
+            verifier_circuit,
+            prover.pk,
+            equivocation_taproot_info,
+            AMOUNT,
+            FEE,
+            DUST_LIMIT,
+        );
+
+        // A wire written by one gate and read by another
+        let (writer, reader) = (0..circuit.gates.len())
+            .find_map(|writer| {
+                let output = circuit.gates[writer].get_output_wires()[0]
+                    .lock()
+                    .unwrap()
+                    .index;
+                (writer + 1..circuit.gates.len())
+                    .find(|&reader| {
+                        circuit.gates[reader]
+                            .get_input_wires()
+                            .iter()
+                            .any(|wire| wire.lock().unwrap().index == output)
+                    })
+                    .map(|reader| (writer, reader))
+            })
+            .unwrap();
+
+        circuit.evaluate(vec![vec![true; 64], vec![false; 64]]);
+        respond(
+            &mut rounds[0],
+            0,
+            &mut circuit,
+            &prover,
+            &challenge_hash_manager,
+            writer,
+        );
+        // The prover flips the output when asked about the gate reading it
+        let output = circuit.gates[writer].get_output_bits();
+        circuit.gates[writer].set_output_bits(vec![!output[0]]);
+        respond(
+            &mut rounds[1],
+            1,
+            &mut circuit,
+            &prover,
+            &challenge_hash_manager,
+            reader,
+        );
+
+        let slashing_txs: Vec<_> = rounds
+            .iter()
+            .enumerate()
+            .map(|(round_num, round)| {
+                detector
+                    .process_response(
+                        &verifier,
+                        &round.next_challenge_tx,
+                        &round.response_tx,
+                        &round.challenge_hashes,
+                        round_num as u64 + 1,
+                    )
+                    .unwrap()
+            })
+            .collect();
+        assert!(slashing_txs[0].is_none());
+        let slashing_tx = slashing_txs[1].as_ref().expect("the prover equivocated");
+        assert_eq!(
+            verify_script_spend(
+                slashing_tx,
+                0,
+                &[rounds[1].next_challenge_tx.output[1].clone()]
+            ),
+            Ok(())
+        );
+    }
+}
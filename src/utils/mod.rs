@@ -1,5 +1,7 @@
 pub mod bitcoin_rpc;
 pub mod challenge_hashes;
 pub mod conversions;
+pub mod equivocation;
 pub mod multisig_cache;
+pub mod setup_payload;
 pub mod witness;
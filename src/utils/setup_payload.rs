@@ -0,0 +1,164 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::circuit::{commitment::CircuitCommitment, wire::HashValue, BristolCircuit};
+
+use super::challenge_hashes::ChallengeHashesManager;
+
+pub const SETUP_PAYLOAD_VERSION: u32 = 1;
+
+/// Everything the prover and verifier exchange during setup and need to persist: the hash pair
+/// of every wire and the challenge hashes of every round. It can be encoded as json or as a
+/// compact binary, both of which start with the version so old payloads can be detected.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SetupPayload {
+    pub version: u32,
+    pub commitment: CircuitCommitment,
+    pub challenge_hashes: Vec<Vec<HashValue>>,
+}
+
+#[derive(Debug)]
+pub enum SetupPayloadError {
+    Json(serde_json::Error),
+    Binary(bincode::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SetupPayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupPayloadError::Json(e) => write!(f, "invalid json setup payload: {}", e),
+            SetupPayloadError::Binary(e) => write!(f, "invalid binary setup payload: {}", e),
+            SetupPayloadError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported setup payload version {}, expected {}",
+                version, SETUP_PAYLOAD_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SetupPayloadError {}
+
+impl From<serde_json::Error> for SetupPayloadError {
+    fn from(e: serde_json::Error) -> Self {
+        SetupPayloadError::Json(e)
+    }
+}
+
+impl From<bincode::Error> for SetupPayloadError {
+    fn from(e: bincode::Error) -> Self {
+        SetupPayloadError::Binary(e)
+    }
+}
+
+/// Only the version is decoded first, so a payload from a newer version is reported as such
+/// rather than as a decoding error
+#[derive(Deserialize)]
+struct VersionOnly {
+    version: u32,
+}
+
+fn check_version(version: u32) -> Result<(), SetupPayloadError> {
+    if version != SETUP_PAYLOAD_VERSION {
+        return Err(SetupPayloadError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+impl SetupPayload {
+    pub fn new(circuit: &BristolCircuit, challenge_hash_manager: &ChallengeHashesManager) -> Self {
+        SetupPayload {
+            version: SETUP_PAYLOAD_VERSION,
+            commitment: circuit.commitment(),
+            challenge_hashes: challenge_hash_manager.challenge_hashes.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("setup payload should serialize to json")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SetupPayloadError> {
+        check_version(serde_json::from_str::<VersionOnly>(json)?.version)?;
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("setup payload should serialize to bytes")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SetupPayloadError> {
+        check_version(bincode::deserialize::<VersionOnly>(bytes)?.version)?;
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{circuit::BristolCircuit, utils::challenge_hashes::ChallengeHashesManager};
+
+    use super::{SetupPayload, SetupPayloadError, SETUP_PAYLOAD_VERSION};
+
+    fn setup_payload() -> SetupPayload {
+        let circuit = BristolCircuit::from_bristol("circuits/add.txt", &[0; 32]).unwrap();
+        let mut challenge_hash_manager = ChallengeHashesManager::new();
+        for i in 0..3 {
+            challenge_hash_manager.generate_challenge_hashes(circuit.gates.len(), Some(i));
+        }
+        SetupPayload::new(&circuit, &challenge_hash_manager)
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let payload = setup_payload();
+        assert_eq!(payload.version, SETUP_PAYLOAD_VERSION);
+        assert_eq!(payload.commitment.wire_hashes.len(), 504);
+        assert_eq!(payload.challenge_hashes.len(), 3);
+
+        let decoded = SetupPayload::from_json(&payload.to_json()).unwrap();
+        assert_eq!(decoded, payload);
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let payload = setup_payload();
+        let bytes = payload.to_bytes();
+
+        // Version, then a length prefixed list of hash pairs and of rounds of 32 byte hashes
+        let expected_len = 4 + (8 + 504 * 64) + (8 + 3 * (8 + 376 * 32));
+        assert_eq!(bytes.len(), expected_len);
+
+        let decoded = SetupPayload::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, payload);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut payload = setup_payload();
+        payload.version = SETUP_PAYLOAD_VERSION + 1;
+
+        assert!(matches!(
+            SetupPayload::from_json(&payload.to_json()),
+            Err(SetupPayloadError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            SetupPayload::from_bytes(&payload.to_bytes()),
+            Err(SetupPayloadError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_payload() {
+        let bytes = setup_payload().to_bytes();
+        assert!(matches!(
+            SetupPayload::from_bytes(&bytes[..100]),
+            Err(SetupPayloadError::Binary(_))
+        ));
+        assert!(matches!(
+            SetupPayload::from_json("{\"version\": 1}"),
+            Err(SetupPayloadError::Json(_))
+        ));
+    }
+}
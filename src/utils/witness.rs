@@ -1,16 +1,94 @@
-use bitcoin::hashes::Hash;
+use std::fmt;
+
+use bitcoin::hashes::{sha256, Hash};
 use bitcoin::secp256k1::schnorr::Signature;
-use bitcoin::taproot::TaprootSpendInfo;
+use bitcoin::taproot::{TaprootSpendInfo, TAPROOT_ANNEX_PREFIX};
 use bitcoin::{sighash::SighashCache, taproot::LeafVersion, TapLeafHash};
-use bitcoin::{Transaction, XOnlyPublicKey};
+use bitcoin::{OutPoint, Transaction, TxOut, Witness, XOnlyPublicKey};
 
-use crate::circuit::wire::{HashTuple, PreimageTuple};
+use crate::circuit::wire::{
+    HashTuple, HashValue, PreimageOutcome, PreimageTuple, PreimageValue, Wire,
+};
 use crate::circuit::BristolCircuit;
-use crate::transactions::{generate_2_of_2_script, generate_anti_contradiction_script};
+use crate::traits::gate::GateTrait;
+use crate::transactions::{
+    generate_2_of_2_script, generate_anti_contradiction_script, generate_gate_response_script,
+};
 use crate::{actor::Actor, transactions::generate_challenge_script};
 
 use super::challenge_hashes::ChallengeHashesManager;
 
+/// Reasons a spend can't be read back as a response to one of the verifier's gate challenges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseWitnessError {
+    /// The transaction doesn't spend the response output
+    MissingResponseInput,
+    /// The revealed hashlock preimage doesn't open any of the round's challenges
+    UnknownChallenge,
+    /// The witness ends with an annex
+    UnsupportedAnnex,
+    /// The revealed script isn't the response leaf of the challenged gate
+    ScriptMismatch {
+        gate: usize,
+    },
+    WrongWitnessLength {
+        expected: usize,
+        found: usize,
+    },
+    /// The spending transaction has an input that doesn't spend one of the response outputs
+    UnknownPrevout {
+        input: usize,
+    },
+    /// The gate's response leaf isn't part of the response output's tree
+    MissingResponseLeaf,
+    /// A witness item where a 32 byte preimage was expected
+    InvalidPreimage {
+        position: usize,
+    },
+    /// A revealed preimage doesn't open its wire's commitment in the challenged gate
+    ForgedPreimage {
+        gate: usize,
+    },
+}
+
+impl fmt::Display for ResponseWitnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseWitnessError::MissingResponseInput => {
+                write!(f, "transaction doesn't spend the response output")
+            }
+            ResponseWitnessError::UnknownChallenge => {
+                write!(f, "hashlock preimage doesn't match any challenge hash")
+            }
+            ResponseWitnessError::UnsupportedAnnex => {
+                write!(f, "response witness carries an annex")
+            }
+            ResponseWitnessError::ScriptMismatch { gate } => {
+                write!(f, "script isn't the response leaf of gate {}", gate)
+            }
+            ResponseWitnessError::UnknownPrevout { input } => {
+                write!(f, "input {} doesn't spend a response output", input)
+            }
+            ResponseWitnessError::MissingResponseLeaf => {
+                write!(f, "gate response leaf isn't in the response output's tree")
+            }
+            ResponseWitnessError::WrongWitnessLength { expected, found } => write!(
+                f,
+                "response witness has {} items, expected {}",
+                found, expected
+            ),
+            ResponseWitnessError::InvalidPreimage { position } => {
+                write!(f, "witness item {} is not a preimage", position)
+            }
+            ResponseWitnessError::ForgedPreimage { gate } => {
+                write!(f, "response to gate {} reveals a forged preimage", gate)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResponseWitnessError {}
+
 /**
 * This function is called by the verifier to fill the response transaction with the witness data
 **/
@@ -106,3 +184,532 @@ pub fn fill_response_tx_with_witness_for_equivocation(
     witness.push(equivocation_script);
     witness.push(&equivocation_control_block.serialize());
 }
+
+/**
+* This function is called by the prover to answer a gate challenge. It fills the input of the
+* next challenge transaction that spends the response output with the gate's response leaf.
+* The gate's wires must hold the values the prover committed to, and every input of the next
+* challenge transaction must spend one of the response transaction's outputs.
+**/
+pub fn fill_challenge_tx_with_witness_for_gate_response(
+    next_challenge_tx: &mut Transaction,
+    response_tx: &Transaction,
+    prover: &Actor,
+    gate: &mut Box<dyn GateTrait>,
+    challenge_preimage: PreimageValue,
+    response_taproot_info: &TaprootSpendInfo,
+) -> Result<(), ResponseWitnessError> {
+    let response_txid = response_tx.txid();
+    let input_index = next_challenge_tx
+        .input
+        .iter()
+        .position(|input| input.previous_output == OutPoint::new(response_txid, 0))
+        .ok_or(ResponseWitnessError::MissingResponseInput)?;
+    let prevouts = next_challenge_tx
+        .input
+        .iter()
+        .enumerate()
+        .map(|(input, txin)| {
+            let outpoint = txin.previous_output;
+            response_tx
+                .output
+                .get(outpoint.vout as usize)
+                .filter(|_| outpoint.txid == response_txid)
+                .cloned()
+                .ok_or(ResponseWitnessError::UnknownPrevout { input })
+        })
+        .collect::<Result<Vec<TxOut>, ResponseWitnessError>>()?;
+
+    let challenge_hash = sha256::Hash::hash(&challenge_preimage).to_byte_array();
+    let response_script = generate_gate_response_script(gate, &challenge_hash, prover.pk);
+    let response_control_block = response_taproot_info
+        .control_block(&(response_script.clone(), LeafVersion::TapScript))
+        .ok_or(ResponseWitnessError::MissingResponseLeaf)?;
+
+    let mut sighash_cache = SighashCache::new(next_challenge_tx);
+
+    let sig_hash = sighash_cache
+        .taproot_script_spend_signature_hash(
+            input_index,
+            &bitcoin::sighash::Prevouts::All(&prevouts),
+            TapLeafHash::from_script(&response_script, LeafVersion::TapScript),
+            bitcoin::sighash::TapSighashType::Default,
+        )
+        .unwrap();
+
+    let response_sig = prover.sign_tx(&sig_hash.to_byte_array());
+
+    // The script checks the hashlock first, then the output and the inputs from the last, so
+    // the preimages go on in the order create_response_witness returns them with the
+    // signature for the final CHECKSIG underneath
+    let witness = sighash_cache.witness_mut(input_index).unwrap();
+    witness.push(response_sig.as_ref());
+    for preimage in gate.create_response_witness(challenge_preimage) {
+        witness.push(preimage);
+    }
+    witness.push(response_script);
+    witness.push(response_control_block.serialize());
+    Ok(())
+}
+
+/// Maps a response leaf spend back to the challenged gate, returning the gate's index and the
+/// preimages the prover revealed for its input and output wires. The witness is laid out as
+/// `fill_challenge_tx_with_witness_for_gate_response` builds it, and the revealed script must
+/// be the gate's leaf for the round's `challenge_hashes`.
+pub fn decode_gate_response_witness(
+    witness: &Witness,
+    circuit: &BristolCircuit,
+    challenge_hashes: &[HashValue],
+    prover_pk: XOnlyPublicKey,
+) -> Result<(usize, Vec<PreimageValue>), ResponseWitnessError> {
+    let items = witness.to_vec();
+    // An annex would shift every item, and the response leaf never uses one
+    if items.len() >= 2 && items.last().unwrap().first() == Some(&TAPROOT_ANNEX_PREFIX) {
+        return Err(ResponseWitnessError::UnsupportedAnnex);
+    }
+    // Signature, hashlock preimage, script and control block around the wire preimages
+    if items.len() < 4 {
+        return Err(ResponseWitnessError::WrongWitnessLength {
+            expected: 4,
+            found: items.len(),
+        });
+    }
+    let hashlock_position = items.len() - 3;
+    let challenge_hash = sha256::Hash::hash(&items[hashlock_position]).to_byte_array();
+    let gate_num = challenge_hashes
+        .iter()
+        .position(|hash| *hash == challenge_hash)
+        .ok_or(ResponseWitnessError::UnknownChallenge)?;
+
+    let gate = circuit
+        .gates
+        .get(gate_num)
+        .ok_or(ResponseWitnessError::UnknownChallenge)?;
+    let response_script = generate_gate_response_script(gate, &challenge_hash, prover_pk);
+    if items[items.len() - 2] != response_script.as_bytes() {
+        return Err(ResponseWitnessError::ScriptMismatch { gate: gate_num });
+    }
+    let expected = gate.get_input_size() + gate.get_output_size() + 4;
+    if items.len() != expected {
+        return Err(ResponseWitnessError::WrongWitnessLength {
+            expected,
+            found: items.len(),
+        });
+    }
+
+    let preimages = (1..hashlock_position)
+        .map(|position| {
+            items[position]
+                .as_slice()
+                .try_into()
+                .map_err(|_| ResponseWitnessError::InvalidPreimage { position })
+        })
+        .collect::<Result<Vec<PreimageValue>, ResponseWitnessError>>()?;
+    Ok((gate_num, preimages))
+}
+
+/**
+* This function is called by the verifier once a spend of the response output confirms. It
+* records the preimages the prover revealed in the circuit's wires and returns the challenged
+* gate along with a wire whose preimages are now both known, if the prover equivocated. A
+* preimage that doesn't open its wire's commitment is a protocol violation and nothing is
+* recorded.
+**/
+pub fn add_preimages_from_gate_response(
+    spending_tx: &Transaction,
+    response_tx: &Transaction,
+    circuit: &mut BristolCircuit,
+    challenge_hashes: &[HashValue],
+    prover_pk: XOnlyPublicKey,
+) -> Result<(usize, Option<Wire>), ResponseWitnessError> {
+    let input = spending_tx
+        .input
+        .iter()
+        .find(|input| input.previous_output == OutPoint::new(response_tx.txid(), 0))
+        .ok_or(ResponseWitnessError::MissingResponseInput)?;
+    let (gate_num, preimages) =
+        decode_gate_response_witness(&input.witness, circuit, challenge_hashes, prover_pk)?;
+    match circuit.gates[gate_num].add_preimages_from_witness(preimages) {
+        PreimageOutcome::Contradiction(wire) => Ok((gate_num, Some(wire))),
+        PreimageOutcome::InvalidPreimage => {
+            Err(ResponseWitnessError::ForgedPreimage { gate: gate_num })
+        }
+        PreimageOutcome::NewBit | PreimageOutcome::AlreadyKnown => Ok((gate_num, None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        hashes::Hash,
+        key::Secp256k1,
+        opcodes::all::OP_EQUALVERIFY,
+        taproot::{TaprootSpendInfo, TAPROOT_ANNEX_PREFIX},
+        OutPoint, Transaction, Txid, Witness,
+    };
+
+    use crate::{
+        actor::{Actor, ActorType},
+        circuit::BristolCircuit,
+        transactions::{
+            challenge::{build_challenge_tx, build_response_tx},
+            generate_2_of_2_script, generate_challenge_address_and_info,
+            generate_equivocation_address_and_info, generate_response_address_and_info,
+            generate_timelock_script,
+            interpreter::{verify_script_spend, ScriptError},
+            taproot_address_from_script_leaves,
+        },
+        utils::challenge_hashes::ChallengeHashesManager,
+    };
+
+    use super::{
+        add_preimages_from_gate_response, fill_challenge_tx_with_witness_for_gate_response,
+        ResponseWitnessError,
+    };
+
+    const AMOUNT: u64 = 100_000;
+    const FEE: u64 = 500;
+    const DUST_LIMIT: u64 = 546;
+
+    /// A response transaction of the first round and the challenge transaction of the second
+    /// round spending it, without touching a node
+    fn response_round(
+        circuit: &BristolCircuit,
+        prover: &Actor,
+        verifier: &Actor,
+        challenge_hashes: &Vec<[u8; 32]>,
+    ) -> (Transaction, TaprootSpendInfo, Transaction) {
+        let secp = Secp256k1::new();
+        let (equivocation_address, _) =
+            generate_equivocation_address_and_info(&secp, circuit, prover.pk, verifier.pk);
+        let (challenge_address, _) =
+            generate_challenge_address_and_info(&secp, circuit, verifier.pk, challenge_hashes);
+        let (response_address, response_taproot_info) =
+            generate_response_address_and_info(&secp, circuit, prover.pk, challenge_hashes);
+        let (response_second_address, _) = taproot_address_from_script_leaves(
+            &secp,
+            vec![
+                generate_timelock_script(verifier.pk, 10),
+                generate_2_of_2_script(prover.pk, verifier.pk),
+            ],
+        );
+
+        let challenge_tx = build_challenge_tx(
+            &Txid::all_zeros(),
+            &challenge_address,
+            &equivocation_address,
+            AMOUNT,
+            FEE,
+            DUST_LIMIT,
+            0,
+            0,
+        );
+        let response_tx = build_response_tx(
+            &challenge_tx,
+            &response_address,
+            &response_second_address,
+            AMOUNT,
+            FEE,
+            DUST_LIMIT,
+            0,
+        );
+        let next_challenge_tx = build_challenge_tx(
+            &response_tx.txid(),
+            &challenge_address,
+            &equivocation_address,
+            AMOUNT,
+            FEE,
+            DUST_LIMIT,
+            1,
+            0,
+        );
+        (response_tx, response_taproot_info, next_challenge_tx)
+    }
+
+    #[test]
+    fn test_gate_response_witness_spends_response_leaf() {
+        let prover = Actor::new(ActorType::Prover, Some(0));
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &[0; 32]).unwrap();
+        circuit.evaluate(vec![vec![true; 64], vec![false; 64]]);
+        let mut challenge_hash_manager = ChallengeHashesManager::new();
+        let (challenge_hashes, _) =
+            challenge_hash_manager.generate_challenge_hashes(circuit.gates.len(), Some(0));
+        let (response_tx, response_taproot_info, next_challenge_tx) =
+            response_round(&circuit, &prover, &verifier, &challenge_hashes);
+
+        for gate_num in [0, 10, circuit.gates.len() - 1] {
+            let mut tx = next_challenge_tx.clone();
+            fill_challenge_tx_with_witness_for_gate_response(
+                &mut tx,
+                &response_tx,
+                &prover,
+                &mut circuit.gates[gate_num],
+                challenge_hash_manager.get_challenge_preimage(0, gate_num),
+                &response_taproot_info,
+            )
+            .unwrap();
+            assert_eq!(verify_script_spend(&tx, 0, &response_tx.output), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_gate_response_witness_rejects_wrong_output() {
+        let prover = Actor::new(ActorType::Prover, Some(0));
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &[0; 32]).unwrap();
+        circuit.evaluate(vec![vec![true; 64], vec![true; 64]]);
+        let mut challenge_hash_manager = ChallengeHashesManager::new();
+        let (challenge_hashes, _) =
+            challenge_hash_manager.generate_challenge_hashes(circuit.gates.len(), Some(0));
+        let (response_tx, response_taproot_info, mut next_challenge_tx) =
+            response_round(&circuit, &prover, &verifier, &challenge_hashes);
+
+        // A prover that lies about the output of the gate can't spend its response leaf
+        let gate = &mut circuit.gates[0];
+        let output = gate.get_output_bits();
+        gate.set_output_bits(vec![!output[0]]);
+        fill_challenge_tx_with_witness_for_gate_response(
+            &mut next_challenge_tx,
+            &response_tx,
+            &prover,
+            gate,
+            challenge_hash_manager.get_challenge_preimage(0, 0),
+            &response_taproot_info,
+        )
+        .unwrap();
+        assert_eq!(
+            verify_script_spend(&next_challenge_tx, 0, &response_tx.output),
+            Err(ScriptError::VerifyFailed(OP_EQUALVERIFY))
+        );
+    }
+
+    #[test]
+    fn test_gate_response_witness_signs_for_every_input() {
+        let prover = Actor::new(ActorType::Prover, Some(0));
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &[0; 32]).unwrap();
+        circuit.evaluate(vec![vec![true; 64], vec![false; 64]]);
+        let mut challenge_hash_manager = ChallengeHashesManager::new();
+        let (challenge_hashes, _) =
+            challenge_hash_manager.generate_challenge_hashes(circuit.gates.len(), Some(0));
+        let (response_tx, response_taproot_info, mut next_challenge_tx) =
+            response_round(&circuit, &prover, &verifier, &challenge_hashes);
+        let challenge_preimage = challenge_hash_manager.get_challenge_preimage(0, 0);
+
+        // The response output doesn't have to be spent by the first input
+        next_challenge_tx.input.swap(0, 1);
+        let mut tx = next_challenge_tx.clone();
+        fill_challenge_tx_with_witness_for_gate_response(
+            &mut tx,
+            &response_tx,
+            &prover,
+            &mut circuit.gates[0],
+            challenge_preimage,
+            &response_taproot_info,
+        )
+        .unwrap();
+        let prevouts = [response_tx.output[1].clone(), response_tx.output[0].clone()];
+        assert_eq!(verify_script_spend(&tx, 1, &prevouts), Ok(()));
+
+        let mut tx = next_challenge_tx.clone();
+        tx.input[0].previous_output = OutPoint::null();
+        assert_eq!(
+            fill_challenge_tx_with_witness_for_gate_response(
+                &mut tx,
+                &response_tx,
+                &prover,
+                &mut circuit.gates[0],
+                challenge_preimage,
+                &response_taproot_info,
+            ),
+            Err(ResponseWitnessError::UnknownPrevout { input: 0 })
+        );
+        assert_eq!(
+            fill_challenge_tx_with_witness_for_gate_response(
+                &mut response_tx.clone(),
+                &response_tx,
+                &prover,
+                &mut circuit.gates[0],
+                challenge_preimage,
+                &response_taproot_info,
+            ),
+            Err(ResponseWitnessError::MissingResponseInput)
+        );
+        assert_eq!(
+            fill_challenge_tx_with_witness_for_gate_response(
+                &mut next_challenge_tx,
+                &response_tx,
+                &prover,
+                &mut circuit.gates[0],
+                [7; 32],
+                &response_taproot_info,
+            ),
+            Err(ResponseWitnessError::MissingResponseLeaf)
+        );
+    }
+
+    #[test]
+    fn test_verifier_learns_revealed_preimages() {
+        let prover = Actor::new(ActorType::Prover, Some(0));
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &[0; 32]).unwrap();
+        let mut verifier_circuit =
+            BristolCircuit::from_bristol("circuits/add.txt", &circuit.commitment()).unwrap();
+        let mut challenge_hash_manager = ChallengeHashesManager::new();
+        let mut respond = |circuit: &mut BristolCircuit, round: u64, gate_num: usize| {
+            let (challenge_hashes, _) =
+                challenge_hash_manager.generate_challenge_hashes(circuit.gates.len(), Some(round));
+            let (response_tx, response_taproot_info, mut next_challenge_tx) =
+                response_round(circuit, &prover, &verifier, &challenge_hashes);
+            fill_challenge_tx_with_witness_for_gate_response(
+                &mut next_challenge_tx,
+                &response_tx,
+                &prover,
+                &mut circuit.gates[gate_num],
+                challenge_hash_manager.get_challenge_preimage(round as usize, gate_num),
+                &response_taproot_info,
+            )
+            .unwrap();
+            (response_tx, next_challenge_tx, challenge_hashes)
+        };
+
+        // The first gate adds the lowest bits of both inputs
+        circuit.evaluate(vec![vec![true; 64], vec![false; 64]]);
+        let (response_tx, spending_tx, challenge_hashes) = respond(&mut circuit, 0, 0);
+        assert_eq!(
+            add_preimages_from_gate_response(
+                &spending_tx,
+                &response_tx,
+                &mut verifier_circuit,
+                &challenge_hashes,
+                prover.pk
+            )
+            .map(|(gate_num, contradiction)| (gate_num, contradiction.is_some())),
+            Ok((0, false))
+        );
+        let gate = &verifier_circuit.gates[0];
+        for wire in gate.get_input_wires().iter().chain(gate.get_output_wires()) {
+            assert!(wire.lock().unwrap().preimages.is_some());
+        }
+
+        // Reading the same response for another round's challenges fails
+        assert_eq!(
+            add_preimages_from_gate_response(
+                &spending_tx,
+                &response_tx,
+                &mut verifier_circuit,
+                &[[0; 32]; 1],
+                prover.pk
+            )
+            .err(),
+            Some(ResponseWitnessError::UnknownChallenge)
+        );
+        assert_eq!(
+            add_preimages_from_gate_response(
+                &response_tx,
+                &response_tx,
+                &mut verifier_circuit,
+                &challenge_hashes,
+                prover.pk
+            )
+            .err(),
+            Some(ResponseWitnessError::MissingResponseInput)
+        );
+
+        // Challenge hashes beyond the circuit's gates don't name a gate
+        let mut padded_hashes = vec![[0; 32]; verifier_circuit.gates.len()];
+        padded_hashes.push(challenge_hashes[0]);
+        assert_eq!(
+            add_preimages_from_gate_response(
+                &spending_tx,
+                &response_tx,
+                &mut verifier_circuit,
+                &padded_hashes,
+                prover.pk
+            )
+            .err(),
+            Some(ResponseWitnessError::UnknownChallenge)
+        );
+
+        let mut with_annex = spending_tx.clone();
+        with_annex.input[0].witness.push([TAPROOT_ANNEX_PREFIX]);
+        assert_eq!(
+            add_preimages_from_gate_response(
+                &with_annex,
+                &response_tx,
+                &mut verifier_circuit,
+                &challenge_hashes,
+                prover.pk
+            )
+            .err(),
+            Some(ResponseWitnessError::UnsupportedAnnex)
+        );
+
+        // A later round answered from a different evaluation reveals the other bit of the
+        // first input wire
+        circuit.evaluate(vec![vec![false; 64], vec![false; 64]]);
+        let (response_tx, spending_tx, challenge_hashes) = respond(&mut circuit, 1, 0);
+        let (gate_num, contradiction) = add_preimages_from_gate_response(
+            &spending_tx,
+            &response_tx,
+            &mut verifier_circuit,
+            &challenge_hashes,
+            prover.pk,
+        )
+        .unwrap();
+        assert_eq!(gate_num, 0);
+        let wire = contradiction.expect("the prover equivocated");
+        let input_wire = verifier_circuit.gates[0].get_input_wires()[0]
+            .lock()
+            .unwrap()
+            .index;
+        assert_eq!(wire.index, input_wire);
+        let preimages = wire.preimages.unwrap();
+        assert!(preimages.zero.is_some() && preimages.one.is_some());
+    }
+
+    #[test]
+    fn test_verifier_rejects_forged_preimages() {
+        let prover = Actor::new(ActorType::Prover, Some(0));
+        let verifier = Actor::new(ActorType::Verifier, Some(1));
+        let mut circuit = BristolCircuit::from_bristol("circuits/add.txt", &[0; 32]).unwrap();
+        let mut verifier_circuit =
+            BristolCircuit::from_bristol("circuits/add.txt", &circuit.commitment()).unwrap();
+        circuit.evaluate(vec![vec![true; 64], vec![false; 64]]);
+        let mut challenge_hash_manager = ChallengeHashesManager::new();
+        let (challenge_hashes, _) =
+            challenge_hash_manager.generate_challenge_hashes(circuit.gates.len(), Some(0));
+        let (response_tx, response_taproot_info, mut next_challenge_tx) =
+            response_round(&circuit, &prover, &verifier, &challenge_hashes);
+        fill_challenge_tx_with_witness_for_gate_response(
+            &mut next_challenge_tx,
+            &response_tx,
+            &prover,
+            &mut circuit.gates[0],
+            challenge_hash_manager.get_challenge_preimage(0, 0),
+            &response_taproot_info,
+        )
+        .unwrap();
+
+        // Arbitrary bytes in place of the output preimage are reported instead of recorded
+        let mut items = next_challenge_tx.input[0].witness.to_vec();
+        items[3] = vec![0; 32];
+        next_challenge_tx.input[0].witness = Witness::from_slice(&items);
+        assert_eq!(
+            add_preimages_from_gate_response(
+                &next_challenge_tx,
+                &response_tx,
+                &mut verifier_circuit,
+                &challenge_hashes,
+                prover.pk
+            )
+            .err(),
+            Some(ResponseWitnessError::ForgedPreimage { gate: 0 })
+        );
+        let gate = &verifier_circuit.gates[0];
+        for wire in gate.get_input_wires().iter().chain(gate.get_output_wires()) {
+            assert!(wire.lock().unwrap().preimages.is_none());
+        }
+    }
+}